@@ -0,0 +1,47 @@
+extern crate lox_lib;
+
+use std::time::{Duration, Instant};
+
+use lox_lib::LoxParser;
+
+const ROUNDS: u32 = 5;
+
+fn bench(name: &str, code: &str, expect: &str) {
+    let mut total = Duration::ZERO;
+    let mut best = Duration::MAX;
+
+    for _ in 0..ROUNDS {
+        let mut lox = LoxParser::new_test();
+        let start = Instant::now();
+        lox.exec_code(code);
+        let used = start.elapsed();
+        assert_eq!(lox.console_take(), expect);
+
+        total += used;
+        best = best.min(used);
+    }
+
+    println!("{:<24} best: {:>10.3?}  avg: {:>10.3?}", name, best, total / ROUNDS);
+}
+
+fn main() {
+    bench("fib(25) recursive", "
+        fun fib(n) {
+            if (n < 2) return n;
+            return fib(n - 1) + fib(n - 2);
+        }
+        print fib(25);
+    ", "75025\n");
+
+    bench("nested blocks", "
+        var sum = 0;
+        for (var i = 0; i < 200; i = i + 1) {
+            {{{{{{{{{{
+                for (var j = 0; j < 200; j = j + 1) {
+                    sum = sum + 1;
+                }
+            }}}}}}}}}}
+        }
+        print sum;
+    ", "40000\n");
+}
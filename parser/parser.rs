@@ -2,10 +2,15 @@ use std::cmp::Ordering;
 use std::io;
 use std::io::Write;
 
+use repl::{command::ReplCommands, reader::LineReader};
+
+use syntax::expression::Expr;
 use syntax::statement::Stmt;
-use syntax::token::Token;
+use syntax::token::{ScanError, Token, Tokens};
 use vm::vm::LoxVM;
 use types::common::Result;
+use types::object::Object;
+use types::shared_str::SharedStrExt;
 
 // use crate::dbg_format;
 
@@ -17,23 +22,61 @@ mod syntax {
 mod types {
     pub mod common;
     pub mod object;
+    pub mod function;
+    pub mod value;
+    pub mod error;
+    pub mod convert;
+    pub mod serialize;
     pub mod number;
     pub mod shared_str;
 }
 
+mod repl {
+    pub mod reader;
+    pub mod command;
+}
+
 mod vm {
     pub mod stack;
     pub mod var_pool;
     pub mod vm;
     pub mod console;
+    pub mod native;
+    pub mod gc;
+    pub mod container;
+    pub mod limits;
+    pub mod json;
+    pub mod pattern;
+    pub mod numeric;
+    pub mod format;
+    pub mod math;
+    pub mod string;
 }
 
+pub use num_bigint::BigInt;
+pub use types::convert::{FromLox, IntoLox, IntoNative, NativeReturn};
+pub use types::error::LoxError;
+pub use rust_decimal::Decimal as ExactDecimal;
+pub use types::number::{DecimalMode, OverflowMode};
+pub use types::value::Value;
+pub use vm::console::{BufferMode, OutputChannel, PrinterSink};
+pub use vm::gc::{GcConfig, GcStats};
+pub use vm::limits::{InterruptHandle, LimitExceeded, VmLimits};
+
 
 #[derive(Debug)]
 pub struct LoxParser {
     prompt: String,
+    prompt_cont: String,
     vm: LoxVM,
     tokens: Vec<Token>,
+    // REPL input inside an unterminated string, not scanned yet
+    pending: String,
+    // source of the REPL input being collected
+    input: String,
+    // REPL input executed successfully, for `.save'
+    session: String,
+    commands: ReplCommands,
 }
 
 // init related
@@ -41,8 +84,13 @@ impl LoxParser {
     pub fn new() -> Self {
         let lox = LoxParser{
             prompt: String::from(">> "),
+            prompt_cont: String::from(".. "),
             vm: LoxVM::new(),
-            tokens: Vec::new()
+            tokens: Vec::new(),
+            pending: String::new(),
+            input: String::new(),
+            session: String::new(),
+            commands: ReplCommands::new(),
         };
 
         lox
@@ -51,8 +99,13 @@ impl LoxParser {
     pub fn new_test() -> Self {
         let mut lox = LoxParser{
             prompt: String::from(">> "),
+            prompt_cont: String::from(".. "),
             vm: LoxVM::new(),
-            tokens: Vec::new()
+            tokens: Vec::new(),
+            pending: String::new(),
+            input: String::new(),
+            session: String::new(),
+            commands: ReplCommands::new(),
         };
         lox.console_disable();
         lox
@@ -63,6 +116,9 @@ impl LoxParser {
         self.vm.clear();
         self.vm.printer.clear();
         self.tokens.clear();
+        self.pending.clear();
+        self.input.clear();
+        self.session.clear();
     }
 }
 
@@ -72,31 +128,59 @@ impl LoxParser {
     #[allow(dead_code)]
     pub fn parse_token_clear(&mut self, code: &str) -> Result<()> {
         self.tokens.clear();
-        syntax::token::scan_from_string(code, &mut self.tokens)
+        Ok(syntax::token::scan_from_string_mode(code, &mut self.tokens, self.vm.decimal)?)
     }
 
     #[allow(dead_code)]
     pub fn parse_token_append(&mut self, code: &str) -> Result<()> {
-        syntax::token::scan_from_string(code, &mut self.tokens)
+        Ok(syntax::token::scan_from_string_mode(code, &mut self.tokens, self.vm.decimal)?)
     }
 
     pub fn parse_stmt(&mut self) -> Result<Stmt> {
-        let (stmt, used) = Stmt::stmt(&self.tokens, 0)?;
+        let (stmt, used) = Stmt::stmt(&Tokens::new(&self.tokens), 0)?;
         self.tokens.drain(0..used);
         Ok(stmt)
     }
 
+    /**
+     * scan and parse the code without executing it
+     */
+    pub fn parse_all(&mut self, code: &str) -> Result<Vec<Stmt>> {
+        self.parse_token_clear(code)?;
+        let mut stmts = Vec::new();
+        while !self.tokens.is_empty() {
+            match self.parse_stmt() {
+                Ok(stmt) => stmts.push(stmt),
+                Err(msg) => {
+                    self.tokens.clear();
+                    return Err(msg);
+                },
+            }
+        }
+        Ok(stmts)
+    }
+
     pub fn exec_stmt(&mut self, stmt: Stmt) -> Result<()> {
-        self.vm.exec(&stmt)?;
+        self.budgeted(|lox| lox.vm.exec(&stmt))?;
         Ok(())
     }
 
     pub fn exec_stmt_all_available(&mut self) -> Result<()> {
-        while !self.tokens.is_empty() {
-            let stmt = self.parse_stmt()?;
-            self.exec_stmt(stmt)?;
-        }
-        Ok(())
+        self.budgeted(|lox| {
+            while !lox.tokens.is_empty() {
+                let stmt = lox.parse_stmt()?;
+                lox.exec_stmt(stmt)?;
+            }
+            Ok(())
+        })
+    }
+
+    // run `f' under the execution budgets, see `VmBudget::run_enter'
+    fn budgeted<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.vm.budget.run_enter();
+        let ret = f(self);
+        self.vm.budget.run_exit();
+        ret
     }
 
 }
@@ -121,25 +205,132 @@ impl LoxParser {
         self.exec_stmt_all_available()
     }
 
-    // #[allow(dead_code)]
-    pub fn repl(&mut self) {
-        let stdin = io::stdin();
+    /**
+     * check if the input collected by the REPL is still waiting for more lines
+     */
+    pub fn repl_is_continuing(&self) -> bool {
+        !self.tokens.is_empty() || !self.pending.is_empty()
+    }
 
-        let mut line = String::new();
-        loop {
-            self.prompt_disp();
-            line.clear();
-            if let Err(msg) = stdin.read_line(&mut line) {
-                self.vm.printer.println(&dbg_format!("{}", msg));
-                return;
+    /**
+     * feed one line of REPL input, the input is collected until it forms
+     * complete statements, then they are executed
+     *
+     * input is incomplete when it ends in a string or in the middle of a
+     * statement, a single expression doesn't need the `;'. other syntax
+     * errors are reported right away
+     *
+     * the value of an expression statement is printed unless it's nil
+     *
+     * ret: Ok(true) if the collected input was executed, Ok(false) if more
+     *      input is needed, Err(msg) if it failed, the input is discarded
+     */
+    pub fn repl_feed(&mut self, line: &str) -> Result<bool> {
+        self.input.push_str(line);
+        self.pending.push_str(line);
+
+        let mut tokens = Vec::new();
+        match syntax::token::scan_from_string_mode(&self.pending, &mut tokens, self.vm.decimal) {
+            Ok(()) => self.tokens.append(&mut tokens),
+            // the string goes on in the next line, scan it again with the rest
+            Err(ScanError::Incomplete(_)) => return Ok(false),
+            Err(err) => {
+                self.repl_discard();
+                return Err(err.into());
+            },
+        }
+        self.pending.clear();
+        if Self::tokens_single_expr(&self.tokens) {
+            self.tokens.push(Token::Semicolon);
+        }
+        if Self::tokens_incomplete(&self.tokens) {
+            return Ok(false);
+        }
+
+        let ret = self.budgeted(Self::exec_stmt_all_available_echo);
+        if ret.is_ok() {
+            self.session.push_str(&self.input);
+        }
+        self.repl_discard();
+        ret.map(|_| true)
+    }
+
+    /**
+     * drop the input collected by the REPL
+     */
+    pub fn repl_discard(&mut self) {
+        self.tokens.clear();
+        self.pending.clear();
+        self.input.clear();
+    }
+
+    fn exec_stmt_all_available_echo(&mut self) -> Result<()> {
+        while !self.tokens.is_empty() {
+            match self.parse_stmt()? {
+                Stmt::Expr(expr) => {
+                    let obj = self.vm.eval(&expr)?;
+                    if !matches!(obj.as_ref(), Object::Nil) {
+                        self.vm.printer.println(&obj.repr());
+                    }
+                },
+                stmt => self.exec_stmt(stmt)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn tokens_single_expr(tokens: &[Token]) -> bool {
+        if matches!(tokens.last(), None | Some(Token::Semicolon | Token::RightBrace)) {
+            return false;
+        }
+        matches!(Expr::expression(&Tokens::new(tokens), 0), Ok((_, used)) if used == tokens.len())
+    }
+
+    // whether a statement fails only because the tokens end before it does
+    fn tokens_incomplete(tokens: &[Token]) -> bool {
+        let mut start = 0;
+        while start < tokens.len() {
+            let tks = Tokens::new(tokens);
+            match Stmt::stmt(&tks, start) {
+                Ok((_, used)) => start += used,
+                Err(_) => return tks.ended(),
             }
-            if Self::is_break_cmd(&line) {
-                return;
+        }
+        false
+    }
+
+    /**
+     * run the REPL, line editing, history and tab completion are
+     * enabled when stdin is a terminal
+     */
+    pub fn repl(&mut self) {
+        let mut reader = LineReader::new();
+        loop {
+            let line = match reader.read_line(self.prompt_current(), self.vm.global_names()) {
+                Err(msg) => {
+                    self.vm.printer.eprintln(&dbg_format!("{}", msg));
+                    break;
+                },
+                Ok(None) => break,
+                Ok(Some(line)) => line,
+            };
+            // input dropped by Ctrl-C
+            if line.is_empty() {
+                self.repl_discard();
+                continue;
             }
-            if let Err(msg) = self.exec_line(&line) {
-                self.vm.printer.println(&format!("{}", msg))
+            let ret = if self.repl_is_continuing() || !Self::is_command(&line) {
+                self.repl_feed(&line).map(|_| ())
+            } else if Self::is_break_cmd(&line) {
+                break;
+            } else {
+                self.command_exec(&line)
+            };
+            if let Err(msg) = ret {
+                self.vm.printer.eprintln(&msg)
             }
         }
+        reader.history_save();
     }
 
 }
@@ -156,8 +347,31 @@ impl LoxParser {
         &self.prompt
     }
 
+    #[allow(dead_code)]
+    pub fn prompt_cont_set(&mut self, prompt: String) {
+        self.prompt_cont = prompt;
+    }
+
+    #[allow(dead_code)]
+    pub fn prompt_cont_get(&self) -> &String {
+        &self.prompt_cont
+    }
+
+    /**
+     * the prompt, or the continuation prompt while collecting
+     * a multi-line input
+     */
+    pub fn prompt_current(&self) -> &str {
+        if self.repl_is_continuing() {
+            &self.prompt_cont
+        } else {
+            &self.prompt
+        }
+    }
+
+    #[allow(dead_code)]
     pub fn prompt_disp(&self) {
-        print!("{}", self.prompt);
+        print!("{}", self.prompt_current());
         io::stdout().flush().unwrap();
     }
 
@@ -176,5 +390,199 @@ impl LoxParser {
         self.vm.printer.take()
     }
 
+    #[allow(dead_code)]
+    pub fn console_take_err(&mut self) -> String {
+        self.vm.printer.take_err()
+    }
+
+    /**
+     * route the output of a channel, script output goes to `Stdout',
+     * errors shown by the REPL go to `Stderr'
+     */
+    pub fn output_sink_set(&mut self, channel: OutputChannel, sink: PrinterSink) {
+        self.vm.printer.sink_set(channel, sink);
+    }
+
+    pub fn output_mode_set(&mut self, mode: BufferMode) {
+        self.vm.printer.mode_set(mode);
+    }
+
+    pub fn output_flush(&mut self) {
+        self.vm.printer.flush();
+    }
+
+}
+
+// numbers related
+impl LoxParser {
+    pub fn overflow_mode(&self) -> OverflowMode {
+        self.vm.overflow
+    }
+
+    /**
+     * set what happens when integer arithmetic overflows
+     */
+    pub fn overflow_mode_set(&mut self, mode: OverflowMode) {
+        self.vm.overflow = mode;
+    }
+
+    /**
+     * seed the generator of `random()' and `random_int()',
+     * the same seed gives the same numbers
+     */
+    pub fn random_seed(&mut self, seed: u64) {
+        self.vm.rng = vm::math::LoxRng::from_seed(seed);
+    }
+
+    pub fn decimal_mode(&self) -> DecimalMode {
+        self.vm.decimal
+    }
+
+    /**
+     * set the type of decimal literals, such as `0.1', in the code
+     * parsed after this call
+     */
+    pub fn decimal_mode_set(&mut self, mode: DecimalMode) {
+        self.vm.decimal = mode;
+    }
+}
+
+// limits related
+impl LoxParser {
+    pub fn call_depth_max(&self) -> usize {
+        self.vm.call_depth_max()
+    }
+
+    /**
+     * set the maximum number of nested function calls, calls beyond this
+     * depth fail with the `StackOverflow' error
+     */
+    pub fn call_depth_max_set(&mut self, depth: usize) {
+        self.vm.call_depth_max_set(depth);
+    }
+
+    pub fn limits(&self) -> &VmLimits {
+        &self.vm.budget.limits
+    }
+
+    /**
+     * set the execution budgets, they apply to every following run,
+     * such as `exec_line`, `exec_code`, `exec_stmt`, `repl_feed` or `call`
+     */
+    pub fn limits_set(&mut self, limits: VmLimits) {
+        self.vm.budget.limits = limits;
+    }
+
+    /**
+     * get a handle to stop the running code from another thread
+     */
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.vm.budget.interrupt.clone()
+    }
+
+    /**
+     * number of steps evaluated by the last run
+     */
+    pub fn steps_used(&self) -> u64 {
+        self.vm.budget.steps()
+    }
+
+    /**
+     * the limit which stopped the last run, None if it was not stopped
+     */
+    pub fn limit_exceeded(&self) -> Option<LimitExceeded> {
+        self.vm.budget.exceeded
+    }
+}
+
+// embedding related
+impl LoxParser {
+
+    /**
+     * call a global lox function from rust
+     *
+     * name: name of the global function
+     * args: the arguments
+     *
+     * ret: the value returned by the function
+     */
+    pub fn call(&mut self, name: &str, args: &[Value]) -> std::result::Result<Value, LoxError> {
+        let fn_name = name.to_string().to_share();
+        let fn_obj = self.vm.global_get(&fn_name)
+            .ok_or_else(|| LoxError::Undefined(name.to_string()))?;
+
+        let expected = match fn_obj.as_ref() {
+            Object::Function(fun) => Some(fun.params.len()),
+            Object::Native(native) => native.arity(),
+            _ => return Err(LoxError::NotCallable(name.to_string())),
+        };
+        if let Some(expected) = expected {
+            if expected != args.len() {
+                return Err(LoxError::Arity { name: name.to_string(), expected, got: args.len() });
+            }
+        }
+
+        let args = args.iter().map(Value::to_rc).collect();
+        match self.budgeted(|lox| lox.vm.call(&fn_name, &fn_obj, args)) {
+            Ok(ret) => Ok(Value::from_rc(ret)),
+            Err(msg) => match self.vm.budget.exceeded {
+                Some(exceeded) => Err(LoxError::Limit(exceeded, msg)),
+                None => Err(LoxError::Runtime(msg)),
+            },
+        }
+    }
+
+    /**
+     * register a rust function as a global native function,
+     * the arguments and the return value are converted automatically
+     *
+     * func: such as `|a: i64, b: String| -> bool'
+     */
+    pub fn native_add<Args>(&mut self, name: &str, func: impl IntoNative<Args>) {
+        self.vm.native_fn_add(name, func);
+    }
+
+    /**
+     * get the value of a global variable
+     */
+    pub fn global_get(&self, name: &str) -> Option<Value> {
+        self.vm.global_get(&name.to_string().to_share()).map(Value::from_rc)
+    }
+
+    /**
+     * define a global variable, overwrite if it exists
+     */
+    pub fn global_set(&mut self, name: &str, value: impl Into<Value>) {
+        self.vm.global_add(name.to_string().to_share(), value.into().to_rc());
+    }
+}
+
+// gc related
+impl LoxParser {
+    pub fn gc_collect(&mut self) -> usize {
+        self.vm.gc_collect()
+    }
+
+    pub fn gc_stats(&self) -> GcStats {
+        self.vm.gc.stats()
+    }
+
+    pub fn gc_config(&self) -> &GcConfig {
+        self.vm.gc.config()
+    }
+
+    pub fn gc_config_set(&mut self, config: GcConfig) {
+        self.vm.gc.config_set(config);
+    }
+}
+
+// regex related
+impl LoxParser {
+    /**
+     * number of compiled patterns kept by this vm
+     */
+    pub fn pattern_cache_len(&self) -> usize {
+        self.vm.patterns.len()
+    }
 }
 
@@ -0,0 +1,249 @@
+use std::{fmt::Debug, time::{Duration, Instant}};
+
+use crate::{
+    dbg_format,
+    syntax::token::scan_from_string_mode,
+    types::{common::{Crc, Result}, object::Object},
+    LoxParser,
+};
+
+
+pub type ReplCmdFn = Crc<dyn Fn(&mut LoxParser, &str) -> Result<()>>;
+
+/**
+ * a REPL meta-command, called as `.name args`
+ */
+#[derive(Clone)]
+pub struct ReplCommand {
+    name: String,
+    help: String,
+    func: ReplCmdFn,
+}
+
+impl Debug for ReplCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<repl command .{}>", self.name)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ReplCommands {
+    commands: Vec<ReplCommand>,
+}
+
+impl ReplCommands {
+    pub fn new() -> Self {
+        let mut cmds = Self::default();
+        cmds.add("help", "show this help", Crc::new(cmd_help));
+        cmds.add("vars", "list global variables and their values", Crc::new(cmd_vars));
+        cmds.add("tokens", "<code>  show the tokens of the code", Crc::new(cmd_tokens));
+        cmds.add("ast", "<code>  show the syntax tree of the code", Crc::new(cmd_ast));
+        cmds.add("load", "<file>  execute a lox file", Crc::new(cmd_load));
+        cmds.add("save", "<file>  save the successful input of this session", Crc::new(cmd_save));
+        cmds.add("reset", "clear all variables and the session", Crc::new(cmd_reset));
+        cmds.add("time", "<code>  execute the code and show the time used", Crc::new(cmd_time));
+        cmds.add("mode", "[option [value]]  show or set vm options", Crc::new(cmd_mode));
+        cmds
+    }
+
+    /**
+     * add a command, replace the exist command with the same name
+     *
+     * name: command name without the leading `.'
+     * help: one line description shown by `.help'
+     */
+    pub fn add(&mut self, name: &str, help: &str, func: ReplCmdFn) {
+        let cmd = ReplCommand {
+            name: name.to_string(),
+            help: help.to_string(),
+            func,
+        };
+        match self.commands.iter_mut().find(|exist| exist.name == name) {
+            Some(exist) => *exist = cmd,
+            None => self.commands.push(cmd),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<ReplCmdFn> {
+        self.commands.iter()
+            .find(|cmd| cmd.name == name)
+            .map(|cmd| cmd.func.clone())
+    }
+}
+
+// commands related
+impl LoxParser {
+
+    /**
+     * register a REPL meta-command
+     *
+     * name: command name without the leading `.'
+     * help: one line description shown by `.help'
+     * func: called with the text after the command name
+     */
+    pub fn command_add(&mut self, name: &str, help: &str, func: impl Fn(&mut LoxParser, &str) -> Result<()> + 'static) {
+        self.commands.add(name, help, Crc::new(func));
+    }
+
+    /**
+     * check if the line is a meta-command
+     */
+    pub fn is_command(line: &str) -> bool {
+        line.trim_start().starts_with('.')
+    }
+
+    /**
+     * execute a meta-command line such as `.load test.lox'
+     */
+    pub fn command_exec(&mut self, line: &str) -> Result<()> {
+        let line = line.trim();
+        let line = match line.strip_prefix('.') {
+            Some(line) => line,
+            None => return Err(dbg_format!("not a command: {}", line)),
+        };
+        let (name, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        match self.commands.get(name) {
+            Some(func) => func(self, args.trim()),
+            None => Err(dbg_format!("unknown command: .{}, try .help", name)),
+        }
+    }
+
+}
+
+fn cmd_help(lox: &mut LoxParser, _args: &str) -> Result<()> {
+    let mut lines = vec![format!("{:<8} quit the REPL", ".q")];
+    for cmd in lox.commands.commands.iter() {
+        lines.push(format!("{:<8} {}", format!(".{}", cmd.name), cmd.help));
+    }
+    for line in lines {
+        lox.vm.printer.println(&line);
+    }
+    Ok(())
+}
+
+fn cmd_vars(lox: &mut LoxParser, _args: &str) -> Result<()> {
+    // builtins and natives are always there, only show variables defined by the user
+    let mut vars: Vec<_> = lox.vm.global_vars().into_iter()
+        .filter(|(name, value)| !lox.vm.is_builtin(name, value) && !matches!(value.as_ref(), Object::Native(_)))
+        .collect();
+    vars.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+    for (name, value) in vars {
+        lox.vm.printer.println(&format!("{} = {}", name, value));
+    }
+    Ok(())
+}
+
+fn cmd_tokens(lox: &mut LoxParser, args: &str) -> Result<()> {
+    let mut tokens = Vec::new();
+    scan_from_string_mode(args, &mut tokens, lox.vm.decimal)?;
+    for tk in tokens {
+        lox.vm.printer.println(&format!("{:?}", tk));
+    }
+    Ok(())
+}
+
+fn cmd_ast(lox: &mut LoxParser, args: &str) -> Result<()> {
+    for stmt in lox.parse_all(args)? {
+        lox.vm.printer.println(&format!("{:#?}", stmt));
+    }
+    Ok(())
+}
+
+fn cmd_load(lox: &mut LoxParser, args: &str) -> Result<()> {
+    let code = std::fs::read_to_string(args)
+        .map_err(|err| dbg_format!("cannot read {}: {}", args, err))?;
+    lox.exec_line(&code)
+}
+
+fn cmd_save(lox: &mut LoxParser, args: &str) -> Result<()> {
+    std::fs::write(args, &lox.session)
+        .map_err(|err| dbg_format!("cannot write {}: {}", args, err))
+}
+
+fn cmd_reset(lox: &mut LoxParser, _args: &str) -> Result<()> {
+    lox.clear();
+    Ok(())
+}
+
+fn cmd_time(lox: &mut LoxParser, args: &str) -> Result<()> {
+    let start = Instant::now();
+    let ret = lox.exec_line(args);
+    let used = start.elapsed();
+    lox.vm.printer.println(&format!("time: {:?}", used));
+    ret
+}
+
+fn mode_values(lox: &LoxParser) -> Vec<(&'static str, String)> {
+    let opt = |val: Option<String>| val.unwrap_or("off".to_string());
+    let limits = lox.limits();
+    let gc = lox.gc_config();
+    vec![
+        ("call_depth", lox.call_depth_max().to_string()),
+        ("max_steps", opt(limits.max_steps.map(|steps| steps.to_string()))),
+        ("timeout_ms", opt(limits.timeout.map(|timeout| timeout.as_millis().to_string()))),
+        ("gc_threshold", gc.threshold.to_string()),
+        ("gc_auto", gc.auto_collect.to_string()),
+        ("overflow", lox.overflow_mode().to_string()),
+        ("decimal", lox.decimal_mode().to_string()),
+    ]
+}
+
+fn mode_parse<T: std::str::FromStr>(name: &str, value: &str) -> Result<T> {
+    value.parse::<T>().map_err(|_| dbg_format!("invalid value for {}: {}", name, value))
+}
+
+fn mode_parse_opt<T: std::str::FromStr>(name: &str, value: &str) -> Result<Option<T>> {
+    match value {
+        "off" => Ok(None),
+        _ => Ok(Some(mode_parse(name, value)?)),
+    }
+}
+
+fn cmd_mode(lox: &mut LoxParser, args: &str) -> Result<()> {
+    let mut args = args.split_whitespace();
+    let (name, value) = match (args.next(), args.next()) {
+        (None, _) => {
+            for (name, value) in mode_values(lox) {
+                lox.vm.printer.println(&format!("{:<14} {}", name, value));
+            }
+            return Ok(());
+        },
+        (Some(name), None) => {
+            let value = mode_values(lox).into_iter()
+                .find(|(exist, _)| *exist == name)
+                .map(|(_, value)| value)
+                .ok_or_else(|| dbg_format!("unknown option: {}", name))?;
+            lox.vm.printer.println(&format!("{:<14} {}", name, value));
+            return Ok(());
+        },
+        (Some(name), Some(value)) => (name, value),
+    };
+
+    match name {
+        "call_depth" => lox.call_depth_max_set(mode_parse(name, value)?),
+        "max_steps" => {
+            let mut limits = lox.limits().clone();
+            limits.max_steps = mode_parse_opt(name, value)?;
+            lox.limits_set(limits);
+        },
+        "timeout_ms" => {
+            let mut limits = lox.limits().clone();
+            limits.timeout = mode_parse_opt(name, value)?.map(Duration::from_millis);
+            lox.limits_set(limits);
+        },
+        "gc_threshold" => {
+            let mut gc = lox.gc_config().clone();
+            gc.threshold = mode_parse(name, value)?;
+            lox.gc_config_set(gc);
+        },
+        "gc_auto" => {
+            let mut gc = lox.gc_config().clone();
+            gc.auto_collect = mode_parse(name, value)?;
+            lox.gc_config_set(gc);
+        },
+        "overflow" => lox.overflow_mode_set(mode_parse(name, value)?),
+        "decimal" => lox.decimal_mode_set(mode_parse(name, value)?),
+        _ => return Err(dbg_format!("unknown option: {}", name)),
+    }
+    Ok(())
+}
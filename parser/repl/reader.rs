@@ -0,0 +1,139 @@
+use std::{
+    io::{self, BufRead, IsTerminal, Write},
+    path::PathBuf,
+};
+
+use rustyline::{
+    completion::Completer, error::ReadlineError, highlight::Highlighter, hint::Hinter,
+    history::DefaultHistory, validate::Validator, Context, Editor, Helper,
+};
+
+use crate::syntax::token::KEYWORDS;
+
+
+const HISTORY_FILE: &str = ".lox_history";
+
+/**
+ * tab completion for keywords and the names of global variables
+ */
+#[derive(Debug, Default)]
+pub struct LoxHelper {
+    names: Vec<String>,
+}
+
+impl LoxHelper {
+    fn candidates(&self, prefix: &str) -> Vec<String> {
+        let mut ret: Vec<String> = KEYWORDS.iter()
+            .map(|kw| kw.to_string())
+            .chain(self.names.iter().cloned())
+            .filter(|word| word.starts_with(prefix))
+            .collect();
+        ret.sort();
+        ret.dedup();
+        ret
+    }
+}
+
+impl Completer for LoxHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos]
+            .rfind(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_'))
+            .map_or(0, |idx| idx + 1);
+        Ok((start, self.candidates(&line[start..pos])))
+    }
+}
+
+impl Hinter for LoxHelper {
+    type Hint = String;
+}
+
+impl Highlighter for LoxHelper {}
+
+impl Validator for LoxHelper {}
+
+impl Helper for LoxHelper {}
+
+/**
+ * read REPL input, with line editing and history when stdin is a terminal,
+ * plain lines from stdin otherwise
+ */
+pub enum LineReader {
+    Plain(io::Stdin),
+    Editor(Box<Editor<LoxHelper, DefaultHistory>>, Option<PathBuf>),
+}
+
+impl LineReader {
+    pub fn new() -> Self {
+        let stdin = io::stdin();
+        if !stdin.is_terminal() {
+            return Self::Plain(stdin);
+        }
+
+        let mut editor = match Editor::new() {
+            Ok(editor) => editor,
+            Err(_) => return Self::Plain(stdin),
+        };
+        editor.set_helper(Some(LoxHelper::default()));
+
+        let history = Self::history_path();
+        if let Some(path) = &history {
+            // the file doesn't exist on the first run
+            let _ = editor.load_history(path);
+        }
+        Self::Editor(Box::new(editor), history)
+    }
+
+    fn history_path() -> Option<PathBuf> {
+        std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .map(|home| PathBuf::from(home).join(HISTORY_FILE))
+    }
+
+    /**
+     * read one line, with the trailing newline
+     *
+     * names: variable names offered by tab completion
+     *
+     * ret: Ok(None) at the end of input, Ok(Some("")) if the user dropped
+     *      the current input with Ctrl-C
+     */
+    pub fn read_line(&mut self, prompt: &str, names: Vec<String>) -> io::Result<Option<String>> {
+        match self {
+            Self::Plain(stdin) => {
+                print!("{}", prompt);
+                io::stdout().flush()?;
+                let mut line = String::new();
+                match stdin.lock().read_line(&mut line)? {
+                    0 => Ok(None),
+                    _ => Ok(Some(line)),
+                }
+            },
+            Self::Editor(editor, _) => {
+                if let Some(helper) = editor.helper_mut() {
+                    helper.names = names;
+                }
+                match editor.readline(prompt) {
+                    Ok(mut line) => {
+                        if !line.trim().is_empty() {
+                            let _ = editor.add_history_entry(line.as_str());
+                        }
+                        line.push('\n');
+                        Ok(Some(line))
+                    },
+                    Err(ReadlineError::Interrupted) => Ok(Some(String::new())),
+                    Err(ReadlineError::Eof) => Ok(None),
+                    Err(ReadlineError::Io(err)) => Err(err),
+                    Err(err) => Err(io::Error::other(err)),
+                }
+            },
+        }
+    }
+
+    pub fn history_save(&mut self) {
+        if let Self::Editor(editor, Some(path)) = self {
+            let _ = editor.save_history(path);
+        }
+    }
+}
@@ -1,40 +1,83 @@
-use crate::{dbg_format, syntax::token::Token, types::{common::Result, shared_str::SharedStr}};
+use crate::{dbg_format, syntax::token::{Token, Tokens}, types::{common::{Crc, Result}, shared_str::SharedStr}};
+
+
+/**
+ * a part of an interpolated string
+ *
+ * Embed: the expression in `${}', with its source and the column of
+ * the `$' in the string (from 1), kept for the error messages
+ */
+#[derive(Debug, Clone)]
+pub enum StrPart {
+    Text(SharedStr),
+    Embed { expr: Crc<Expr>, source: SharedStr, column: usize },
+}
+
+impl StrPart {
+    /**
+     * point the error to the embedded expression
+     */
+    pub fn error(source: &str, column: usize, err: &str) -> String {
+        dbg_format!("in `${{{}}}` at column {} of the string: {}", source, column, err)
+    }
+}
 
 
 #[derive(Debug)]
 pub enum Expr {
     Assign(Token, Box<Expr>),
+    // name op= value, op is the binary operator
+    Compound(Token, Token, Box<Expr>),
+    // ++name/--name if prefix, name++/name-- if not, op is Plus or Minus
+    Incr(Token, Token, bool),
     Binary(Box<Expr>, Token, Box<Expr>),
     Group (Box<Expr>),
     Literal (Token),
     Unary (Token, Box<Expr>),
-    FnCall(SharedStr, Vec<Box<Expr>>),
+    // cond ? then : else
+    Ternary(Box<Expr>, Box<Expr>, Box<Expr>),
+    FnCall(SharedStr, Vec<Expr>),
+    // object[index]
+    Index(Box<Expr>, Box<Expr>),
+    // object[start:end], both can be omitted
+    Slice(Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>),
+    // object.method(args), calls the builtin `method(object, args)'
+    Method(Box<Expr>, SharedStr, Vec<Expr>),
+    // string with embedded expressions
+    Interp(Crc<Vec<StrPart>>),
 }
 
 impl Clone for Expr {
     fn clone(&self) -> Self {
         match self {
             Self::Assign(arg0, arg1) => Self::Assign(arg0.clone(), arg1.clone()),
+            Self::Compound(arg0, arg1, arg2) => Self::Compound(arg0.clone(), arg1.clone(), arg2.clone()),
+            Self::Incr(arg0, arg1, arg2) => Self::Incr(arg0.clone(), arg1.clone(), *arg2),
             Self::Binary(arg0, arg1, arg2) => Self::Binary(arg0.clone(), arg1.clone(), arg2.clone()),
             Self::Group(arg0) => Self::Group(arg0.clone()),
             Self::Literal(arg0) => Self::Literal(arg0.clone()),
             Self::Unary(arg0, arg1) => Self::Unary(arg0.clone(), arg1.clone()),
+            Self::Ternary(arg0, arg1, arg2) => Self::Ternary(arg0.clone(), arg1.clone(), arg2.clone()),
             Self::FnCall(arg0, arg1) => Self::FnCall(arg0.clone(), arg1.clone()),
+            Self::Index(arg0, arg1) => Self::Index(arg0.clone(), arg1.clone()),
+            Self::Slice(arg0, arg1, arg2) => Self::Slice(arg0.clone(), arg1.clone(), arg2.clone()),
+            Self::Method(arg0, arg1, arg2) => Self::Method(arg0.clone(), arg1.clone(), arg2.clone()),
+            Self::Interp(arg0) => Self::Interp(arg0.clone()),
         }
     }
 }
 
 // parsing methods
 impl Expr {
-    pub fn expression(tks: &Vec<Token>, start: usize) -> Result<(Self, usize)> {
+    pub fn expression(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
         if let Ok(ret) = Self::assign(tks, start) {
             Ok(ret)
         } else {
-            Self::logic_or(tks, start)
+            Self::ternary(tks, start)
         }
     }
 
-    pub fn assign(tks: &Vec<Token>, start: usize) -> Result<(Self, usize)> {
+    pub fn assign(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
         let mut ret_adv = 0;
         let idt = tks.get(start+ret_adv);
         if !matches!(idt, Some(Token::Identifier(_))) {
@@ -42,31 +85,29 @@ impl Expr {
         }
         let idt = idt.unwrap();
         ret_adv+=1;
-        if !matches!(tks.get(start+ ret_adv), Some(Token::Equal)) {
-            return Err(dbg_format!("missing token : Equal"));
-        }
+        // None for the plain assignment
+        let op = match tks.get(start+ ret_adv) {
+            Some(Token::Equal) => None,
+            Some(Token::PlusEqual) => Some(Token::Plus),
+            Some(Token::MinusEqual) => Some(Token::Minus),
+            Some(Token::StarEqual) => Some(Token::Star),
+            Some(Token::SlashEqual) => Some(Token::Slash),
+            Some(Token::PercentEqual) => Some(Token::Percent),
+            _ => return Err(dbg_format!("missing token : Equal")),
+        };
         ret_adv+=1;
 
-        match Self::assign(tks, start + ret_adv) {
-            Ok((expr, used)) => {
-                ret_adv += used;
-                Ok((Expr::Assign(idt.clone(), Box::new(expr)), ret_adv))
-            },
-            Err(_) => {
-                match Self::equality(tks, start+ret_adv) {
-                    Ok((expr, used)) => {
-                        ret_adv += used;
-                        Ok((Expr::Assign(idt.clone(), Box::new(expr)), ret_adv))
-                    },
-                    Err(msg) => Err(msg),
-                }
-            }
+        let (expr, used) = Self::expression(tks, start + ret_adv)?;
+        ret_adv += used;
+        match op {
+            None => Ok((Expr::Assign(idt.clone(), Box::new(expr)), ret_adv)),
+            Some(op) => Ok((Expr::Compound(idt.clone(), op, Box::new(expr)), ret_adv)),
         }
     }
 
     fn binary_common(
-        tks: &Vec<Token>, start: usize,
-        next_fn: fn(&Vec<Token>, usize) -> Result<(Self, usize)>,
+        tks: &Tokens, start: usize,
+        next_fn: fn(&Tokens, usize) -> Result<(Self, usize)>,
         ops: &[Token]
     ) -> Result<(Self, usize)> {
         let (mut expr, adv) = next_fn(tks, start)?;
@@ -89,36 +130,65 @@ impl Expr {
         Ok((expr, ret_adv))
     }
 
+    /**
+     * cond ? then : else, right associative,
+     * `then' can be any expression as in C
+     */
+    pub fn ternary(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
+        let (cond, mut ret_adv) = Self::coalesce(tks, start)?;
+        if !matches!(tks.get(start+ret_adv), Some(Token::Question)) {
+            return Ok((cond, ret_adv));
+        }
+        ret_adv += 1;
+
+        let (then, used) = Self::expression(tks, start+ret_adv)?;
+        ret_adv += used;
+        match tks.get(start+ret_adv) {
+            Some(Token::Colon) => ret_adv += 1,
+            tk => return Err(dbg_format!("expected token : of `?', but got {:#?}", tk)),
+        }
+        let (other, used) = Self::ternary(tks, start+ret_adv)?;
+        ret_adv += used;
+
+        Ok((Expr::Ternary(Box::new(cond), Box::new(then), Box::new(other)), ret_adv))
+    }
+
+    // a ?? b: b only if a is nil
+    const COALESCE_OPS: [Token; 1] = [Token::QuestionQuestion];
+    pub fn coalesce(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
+        Self::binary_common(tks, start, Self::logic_or, &Self::COALESCE_OPS)
+    }
+
     const LOGIC_OR_OPS: [Token; 1] = [Token::Or];
-    pub fn logic_or(tks: &Vec<Token>, start: usize) -> Result<(Self, usize)> {
+    pub fn logic_or(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
         Self::binary_common(tks, start, Self::logic_and, &Self::LOGIC_OR_OPS)
     }
 
     const LOGIC_AND_OPS: [Token; 1] = [Token::And];
-    pub fn logic_and(tks: &Vec<Token>, start: usize) -> Result<(Self, usize)> {
+    pub fn logic_and(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
         Self::binary_common(tks, start, Self::equality, &Self::LOGIC_AND_OPS)
     }
 
     const EQUALITY_OPS: [Token; 2] = [Token::EqualEqual, Token::BangEqual];
-    pub fn equality(tks: &Vec<Token>, start: usize) -> Result<(Self, usize)> {
+    pub fn equality(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
         Self::binary_common(tks, start, Self::comparison, &Self::EQUALITY_OPS)
     }
 
     const COMPARISON_OPS: [Token; 4] = [Token::Greater, Token::GreaterEqual, Token::Less, Token::LessEqual];
-    pub fn comparison(tks: &Vec<Token>, start: usize) -> Result<(Self, usize)> {
+    pub fn comparison(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
         Self::binary_common(tks, start, Self::term, &Self::COMPARISON_OPS)
     }
     const TERM_OPS: [Token; 2] = [Token::Minus, Token::Plus];
-    pub fn term(tks: &Vec<Token>, start: usize) -> Result<(Self, usize)> {
+    pub fn term(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
         Self::binary_common(tks, start, Self::factor, &Self::TERM_OPS)
     }
 
-    const FACTOR_OPS: [Token; 2] = [Token::Slash, Token::Star];
-    pub fn factor(tks: &Vec<Token>, start: usize) -> Result<(Self, usize)> {
+    const FACTOR_OPS: [Token; 3] = [Token::Slash, Token::Star, Token::Percent];
+    pub fn factor(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
         Self::binary_common(tks, start, Self::unary, &Self::FACTOR_OPS)
     }
 
-    pub fn unary(tks: &Vec<Token>, start: usize) -> Result<(Self, usize)> {
+    pub fn unary(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
 
         let tk_start = tks.get(start);
         if let tk_op@ Some(Token::Bang | Token::Minus) = tk_start {
@@ -126,16 +196,93 @@ impl Expr {
             return Ok((Expr::Unary(tk_op.unwrap().clone(), Box::new(expr)), 1+adv));      // !/- +
         }
 
-        if let Some(Token::Identifier(_)) = tk_start {
-            if let Ok((expr, used)) = Self::fn_call(tks, start) {
-                return Ok((expr, used));
+        if let Some(tk_op @ (Token::PlusPlus | Token::MinusMinus)) = tk_start {
+            return match tks.get(start+1) {
+                Some(idt @ Token::Identifier(_)) => Ok((Expr::Incr(idt.clone(), Self::step_op(tk_op), true), 2)),
+                tk => Err(dbg_format!("expected variable after {:?}, but got {:#?}", tk_op, tk)),
+            };
+        }
+
+        let (expr, adv) = Self::call(tks, start)?;
+        match (&expr, tks.get(start+adv)) {
+            (Expr::Literal(idt @ Token::Identifier(_)), Some(tk_op @ (Token::PlusPlus | Token::MinusMinus))) => {
+                Ok((Expr::Incr(idt.clone(), Self::step_op(tk_op), false), adv+1))
+            },
+            _ => Ok((expr, adv)),
+        }
+    }
+
+    // binary operator of `++' and `--'
+    fn step_op(tk: &Token) -> Token {
+        match tk {
+            Token::PlusPlus => Token::Plus,
+            _ => Token::Minus,
+        }
+    }
+
+    /**
+     * function calls, indexing, slicing and method calls
+     */
+    pub fn call(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
+        let (mut expr, mut ret_adv) = match Self::fn_call(tks, start) {
+            Ok(ret) => ret,
+            Err(_) => Self::primary(tks, start)?,
+        };
+
+        loop {
+            match tks.get(start+ret_adv) {
+                Some(Token::LeftBracket) => {
+                    let (index, used) = Self::index_parse(expr, tks, start+ret_adv)?;
+                    expr = index;
+                    ret_adv += used;
+                },
+                Some(Token::Dot) => {
+                    let name = match tks.get(start+ret_adv+1) {
+                        Some(Token::Identifier(name)) => name.clone(),
+                        tk => return Err(dbg_format!("expected method name after `.', but got {:#?}", tk)),
+                    };
+                    let (args, used) = Self::fn_args_parse(tks, start+ret_adv+2)?;
+                    expr = Expr::Method(Box::new(expr), name, args);
+                    ret_adv += 2 + used;     // dot and name
+                },
+                _ => break,
             }
         }
+        Ok((expr, ret_adv))
+    }
+
+    // `[index]' or `[start:end]' after the object
+    fn index_parse(obj: Self, tks: &Tokens, start: usize) -> Result<(Self, usize)> {
+        let mut ret_adv = 1;    // [
+        let bound = |ret_adv: &mut usize| match tks.get(start + *ret_adv) {
+            Some(Token::Colon | Token::RightBracket) => Ok(None),
+            _ => {
+                let (expr, used) = Self::expression(tks, start + *ret_adv)?;
+                *ret_adv += used;
+                Ok::<_, String>(Some(Box::new(expr)))
+            },
+        };
 
-        Self::primary(tks, start)
+        let first = bound(&mut ret_adv)?;
+        let expr = match tks.get(start+ret_adv) {
+            Some(Token::Colon) => {
+                ret_adv += 1;
+                let end = bound(&mut ret_adv)?;
+                Expr::Slice(Box::new(obj), first, end)
+            },
+            _ => match first {
+                Some(index) => Expr::Index(Box::new(obj), index),
+                None => return Err(dbg_format!("missing index in `[]'")),
+            },
+        };
+
+        match tks.get(start+ret_adv) {
+            Some(Token::RightBracket) => Ok((expr, ret_adv + 1)),
+            tk => Err(dbg_format!("expected token ], but got {:#?}", tk)),
+        }
     }
 
-    fn fn_args_parse(tks: &Vec<Token>, start: usize) -> Result<(Vec<Box<Self>>, usize)> {
+    fn fn_args_parse(tks: &Tokens, start: usize) -> Result<(Vec<Self>, usize)> {
         let mut ret_adv = 0;
         match tks.get(start+ret_adv) {
             Some(Token::LeftParen) => ret_adv += 1,
@@ -145,7 +292,7 @@ impl Expr {
         let mut args = Vec::new();
 
         if let Ok((expr, used)) = Self::expression(tks, start+ ret_adv) {
-            args.push(Box::new(expr));
+            args.push(expr);
             ret_adv += used;
             while let Some(Token::Comma) = tks.get(start+ret_adv) {
                 if args.len() >= 255 {
@@ -153,7 +300,7 @@ impl Expr {
                 }
                 ret_adv += 1;
                 let (expr, used) = Self::expression(tks, start+ret_adv)?;
-                args.push(Box::new(expr));
+                args.push(expr);
                 ret_adv += used;
             }
         }
@@ -166,7 +313,7 @@ impl Expr {
         Ok((args, ret_adv))
     }
 
-    pub fn fn_call(tks: &Vec<Token>, start: usize) -> Result<(Self, usize)> {
+    pub fn fn_call(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
         let mut ret_adv = 0;
         let fn_name;
         match tks.get(start) {
@@ -183,11 +330,12 @@ impl Expr {
         Ok((Expr::FnCall(fn_name, args), ret_adv))
     }
 
-    pub fn primary(tks: &Vec<Token>, start: usize) -> Result<(Self, usize)> {
+    pub fn primary(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
         match tks.get(start) {
             tk @ Some(Token::False | Token::True | Token::Nil) => Ok((Expr::Literal(tk.unwrap().clone()), 1)),
             tk @ Some(Token::String(_) | Token::Number(_)) => Ok((Expr::Literal(tk.unwrap().clone()), 1)),
             tk @ Some(Token::Identifier(_)) => Ok((Expr::Literal(tk.unwrap().clone()), 1)),
+            Some(Token::Interp(parts)) => Ok((Expr::Interp(parts.clone()), 1)),
             Some(Token::LeftParen) => {
                 let (expr, adv) = Self::expression(tks, start+1)?;
 
@@ -202,8 +350,22 @@ impl Expr {
         }
     }
 
+    /**
+     * parse an expression embedded in a string, all tokens must be used
+     */
+    pub fn embedded(tks: &Tokens) -> Result<Self> {
+        if tks.is_empty() {
+            return Err(dbg_format!("empty expression"));
+        }
+        let (expr, used) = Self::expression(tks, 0)?;
+        match tks.get(used) {
+            None => Ok(expr),
+            Some(tk) => Err(dbg_format!("unexpected token: {:#?}", tk)),
+        }
+    }
+
     #[allow(dead_code)]
-    pub fn synchronize(tks: &Vec<Token>, start: usize) -> usize {
+    pub fn synchronize(tks: &Tokens, start: usize) -> usize {
         let mut idx: usize = 0;
         while let Some(tk) = tks.get(start + idx) {
             match tk {
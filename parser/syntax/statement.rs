@@ -1,6 +1,6 @@
-use crate::{dbg_format, types::{common::Result, shared_str::SharedStr}};
+use crate::{dbg_format, types::{common::{Crc, Result}, function::{LoxFunction, LoxFunctionRc}}};
 
-use super::{expression::Expr, token::Token};
+use super::{expression::Expr, token::{Token, Tokens}};
 
 
 #[derive(Debug)]
@@ -12,7 +12,8 @@ pub enum Stmt{
     If(Expr, Box<Stmt>, Option<Box<Stmt>>),
     While(Expr, Box<Stmt>),
     For(Option<Box<Stmt>>, Option<Expr>, Option<Expr>, Box<Stmt>),
-    FunDecl(SharedStr, Vec<SharedStr>, Box<Stmt>),
+    FunDecl(LoxFunctionRc),
+    Return(Option<Expr>),
 }
 
 impl Clone for Stmt {
@@ -25,14 +26,15 @@ impl Clone for Stmt {
             Self::If(arg0, arg1, arg2) => Self::If(arg0.clone(), arg1.clone(), arg2.clone()),
             Self::While(arg0, arg1) => Self::While(arg0.clone(), arg1.clone()),
             Self::For(arg0, arg1, arg2, arg3) => Self::For(arg0.clone(), arg1.clone(), arg2.clone(), arg3.clone()),
-            Self::FunDecl(arg0, arg1, arg2) => Self::FunDecl(arg0.clone(), arg1.clone(), arg2.clone()),
+            Self::FunDecl(arg0) => Self::FunDecl(arg0.clone()),
+            Self::Return(arg0) => Self::Return(arg0.clone()),
         }
     }
 }
 
 impl Stmt {
 
-    pub fn stmt(tks: &Vec<Token>, start: usize) -> Result<(Self, usize)> {
+    pub fn stmt(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
         match tks.get(start) {
             Some(Token::Print) => {
                 let (stmt, used) = Self::print(tks, start)?;
@@ -47,12 +49,13 @@ impl Stmt {
             Some(Token::For) => Ok(Self::ctrl_for(tks, start)?),
             Some(Token::LeftBrace) => Ok(Self::block(tks, start)?),
             Some(Token::Fun) => Self::fun_decl(tks, start),
+            Some(Token::Return) => Self::ctrl_return(tks, start),
             Some(_) => Self::expr(tks, start),
             None => Err(dbg_format!("Failed to get token from list")),
         }
     }
 
-    pub fn fun_decl(tks: &Vec<Token>, start: usize) -> Result<(Self, usize)> {
+    pub fn fun_decl(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
         if !matches!(tks.get(start), Some(Token::Fun)) {
             return Err(dbg_format!("not start with Token: fun"));
         }
@@ -63,7 +66,8 @@ impl Stmt {
                 fn_name = name.clone();
                 ret_adv += 1;
             },
-            _ => return Err(dbg_format!("function name expected!!")),
+            Some(tk) => return Err(dbg_format!("function name expected, got `{}`", tk)),
+            None => return Err(dbg_format!("function name expected")),
         }
         match tks.get(start+ret_adv) {
             Some(Token::LeftParen) => ret_adv += 1,
@@ -94,10 +98,10 @@ impl Stmt {
         let (fn_body, used) = Self::block(tks, start+ret_adv)?;
         ret_adv += used;
 
-        Ok((Stmt::FunDecl(fn_name, args, Box::new(fn_body)), ret_adv))
+        Ok((Stmt::FunDecl(Crc::new(LoxFunction::new(fn_name, args, fn_body))), ret_adv))
     }
 
-    pub fn ctrl_for(tks: &Vec<Token>, start: usize) -> Result<(Self, usize)> {
+    pub fn ctrl_for(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
         if !matches!(tks.get(start), Some(Token::For)) {
             return Err(dbg_format!("not start with Token: while"));
         }
@@ -155,7 +159,7 @@ impl Stmt {
         Ok((Stmt::For(opt_start, opt_cont, opt_every, Box::new(stmt_body)), ret_adv))
     }
 
-    pub fn ctrl_while(tks: &Vec<Token>, start: usize) -> Result<(Self, usize)> {
+    pub fn ctrl_while(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
         if !matches!(tks.get(start), Some(Token::While)) {
             return Err(dbg_format!("not start with Token: while"));
         }
@@ -178,7 +182,7 @@ impl Stmt {
         Ok((Stmt::While(expr_cont, Box::new(stmt_true)), ret_adv))
     }
 
-    pub fn ctrl_if(tks: &Vec<Token>, start: usize) -> Result<(Self, usize)> {
+    pub fn ctrl_if(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
         if !matches!(tks.get(start), Some(Token::If)) {
             return Err(dbg_format!("not start with Token: if"));
         }
@@ -209,7 +213,7 @@ impl Stmt {
 
     }
 
-    pub fn block(tks: &Vec<Token>, start: usize) -> Result<(Self, usize)> {
+    pub fn block(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
         if !matches!(tks.get(start), Some(Token::LeftBrace)) {
             return Err(dbg_format!("not start with Token: {"));
         }
@@ -229,7 +233,7 @@ impl Stmt {
         Ok((Self::Block(stmt_arr), ret_adv))
     }
 
-    pub fn decl(tks: &Vec<Token>, start: usize) -> Result<(Self, usize)> {
+    pub fn decl(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
 
         if !matches!(tks.get(start), Some(Token::Var)) {
             return Err(dbg_format!("not start with Token: Var"));
@@ -266,7 +270,7 @@ impl Stmt {
 
     }
 
-    pub fn expr(tks: &Vec<Token>, start: usize) -> Result<(Self, usize)> {
+    pub fn expr(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
         let (expr, adv) = Expr::expression(tks, start)?;
 
         match tks.get(start + adv) {
@@ -275,7 +279,26 @@ impl Stmt {
         }
     }
 
-    pub fn print(tks: &Vec<Token>, start: usize) -> Result<(Self, usize)> {
+    pub fn ctrl_return(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
+        if !matches!(tks.get(start), Some(Token::Return)) {
+            return Err(dbg_format!("not start with Token: return"));
+        }
+        let mut ret_adv = 1;
+
+        let mut opt_value = None;
+        if !matches!(tks.get(start + ret_adv), Some(Token::Semicolon)) {
+            let (expr, used) = Expr::expression(tks, start + ret_adv)?;
+            opt_value = Some(expr);
+            ret_adv += used;
+        }
+
+        match tks.get(start + ret_adv) {
+            Some(Token::Semicolon) => Ok((Stmt::Return(opt_value), ret_adv+1)),
+            tk => Err(dbg_format!("expected ;, but got {:#?}", tk)),
+        }
+    }
+
+    pub fn print(tks: &Tokens, start: usize) -> Result<(Self, usize)> {
         if !matches!(tks.get(start), Some(Token::Print)) {
             return Err(dbg_format!("not start with Token: Print"));
         }
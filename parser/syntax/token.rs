@@ -1,5 +1,6 @@
+use std::{cell::Cell, fmt::Display, iter::Peekable, str::Chars};
 
-use crate::{dbg_format, types::{common::{Crc, Result}, number::Number, shared_str::{SharedStr, SharedStrExt}}};
+use crate::{dbg_format, syntax::expression::{Expr, StrPart}, types::{common::{Crc, Result}, number::{DecimalMode, Number}, shared_str::{SharedStr, SharedStrExt}}};
 
 #[derive(Debug)]
 pub enum Token {
@@ -7,6 +8,10 @@ pub enum Token {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
+    Colon,
+    Question,
     Comma,
     Dot,
     Semicolon,
@@ -14,6 +19,7 @@ pub enum Token {
     Plus,
     Slash,
     Star,
+    Percent,
 
     // One or two character tokens.
     Bang,
@@ -24,11 +30,21 @@ pub enum Token {
     GreaterEqual,
     Less,
     LessEqual,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+    PercentEqual,
+    PlusPlus,
+    MinusMinus,
+    QuestionQuestion,
 
     // Literals.
     Identifier(SharedStr),
     String(SharedStr),
     Number(Number),
+    // string with `${expr}' in it
+    Interp(Crc<Vec<StrPart>>),
 
     // Keywords.
     And,
@@ -64,6 +80,10 @@ impl Clone for Token {
             Self::RightParen => Self::RightParen,
             Self::LeftBrace => Self::LeftBrace,
             Self::RightBrace => Self::RightBrace,
+            Self::LeftBracket => Self::LeftBracket,
+            Self::RightBracket => Self::RightBracket,
+            Self::Colon => Self::Colon,
+            Self::Question => Self::Question,
             Self::Comma => Self::Comma,
             Self::Dot => Self::Dot,
             Self::Semicolon => Self::Semicolon,
@@ -71,6 +91,7 @@ impl Clone for Token {
             Self::Plus => Self::Plus,
             Self::Slash => Self::Slash,
             Self::Star => Self::Star,
+            Self::Percent => Self::Percent,
             Self::Bang => Self::Bang,
             Self::BangEqual => Self::BangEqual,
             Self::Equal => Self::Equal,
@@ -79,9 +100,18 @@ impl Clone for Token {
             Self::GreaterEqual => Self::GreaterEqual,
             Self::Less => Self::Less,
             Self::LessEqual => Self::LessEqual,
+            Self::PlusEqual => Self::PlusEqual,
+            Self::MinusEqual => Self::MinusEqual,
+            Self::StarEqual => Self::StarEqual,
+            Self::SlashEqual => Self::SlashEqual,
+            Self::PercentEqual => Self::PercentEqual,
+            Self::PlusPlus => Self::PlusPlus,
+            Self::MinusMinus => Self::MinusMinus,
+            Self::QuestionQuestion => Self::QuestionQuestion,
             Self::Identifier(arg0) => Self::Identifier(arg0.clone()),
             Self::String(arg0) => Self::String(arg0.clone()),
             Self::Number(arg0) => Self::Number(arg0.clone()),
+            Self::Interp(arg0) => Self::Interp(arg0.clone()),
             Self::And => Self::And,
             Self::Class => Self::Class,
             Self::Else => Self::Else,
@@ -107,6 +137,68 @@ impl Clone for Token {
     }
 }
 
+// the source text of the token, used by error messages
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            Self::LeftParen => "(",
+            Self::RightParen => ")",
+            Self::LeftBrace => "{",
+            Self::RightBrace => "}",
+            Self::LeftBracket => "[",
+            Self::RightBracket => "]",
+            Self::Colon => ":",
+            Self::Question => "?",
+            Self::Comma => ",",
+            Self::Dot => ".",
+            Self::Semicolon => ";",
+            Self::Minus => "-",
+            Self::Plus => "+",
+            Self::Slash => "/",
+            Self::Star => "*",
+            Self::Percent => "%",
+            Self::Bang => "!",
+            Self::BangEqual => "!=",
+            Self::Equal => "=",
+            Self::EqualEqual => "==",
+            Self::Greater => ">",
+            Self::GreaterEqual => ">=",
+            Self::Less => "<",
+            Self::LessEqual => "<=",
+            Self::PlusEqual => "+=",
+            Self::MinusEqual => "-=",
+            Self::StarEqual => "*=",
+            Self::SlashEqual => "/=",
+            Self::PercentEqual => "%=",
+            Self::PlusPlus => "++",
+            Self::MinusMinus => "--",
+            Self::QuestionQuestion => "??",
+            Self::Identifier(name) => return write!(f, "{}", name),
+            Self::String(str) => return write!(f, "\"{}\"", str),
+            Self::Number(num) => return write!(f, "{}", num),
+            Self::Interp(_) => "\"${...}\"",
+            Self::And => "and",
+            Self::Class => "class",
+            Self::Else => "else",
+            Self::False => "false",
+            Self::Fun => "fun",
+            Self::For => "for",
+            Self::If => "if",
+            Self::Nil => "nil",
+            Self::Or => "or",
+            Self::Print => "print",
+            Self::Return => "return",
+            Self::Super => "super",
+            Self::This => "this",
+            Self::True => "true",
+            Self::Var => "var",
+            Self::While => "while",
+            Self::EOF => "end of input",
+        };
+        write!(f, "{}", text)
+    }
+}
+
 impl PartialEq for Token {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
@@ -122,25 +214,243 @@ impl PartialEq for Token {
     }
 }
 
-enum ParseType {
-    Identifier,
-    Number,
+/**
+ * the tokens seen by the parser, it remembers if the parser
+ * looked past the last one, so an error there means the code
+ * is incomplete rather than wrong
+ */
+pub struct Tokens<'a> {
+    list: &'a [Token],
+    ended: Cell<bool>,
+}
+
+impl<'a> Tokens<'a> {
+    pub fn new(list: &'a [Token]) -> Self {
+        Self { list, ended: Cell::new(false) }
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&'a Token> {
+        let tk = self.list.get(idx);
+        if tk.is_none() {
+            self.ended.set(true);
+        }
+        tk
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn ended(&self) -> bool {
+        self.ended.get()
+    }
+}
+
+/**
+ * errors of the scanner, the code is `Incomplete' when it
+ * ends inside a string, which may go on in the next line
+ */
+#[derive(Debug, PartialEq)]
+pub enum ScanError {
+    Incomplete(String),
+    Invalid(String),
+}
+
+impl From<String> for ScanError {
+    fn from(msg: String) -> Self {
+        Self::Invalid(msg)
+    }
+}
+
+impl From<ScanError> for String {
+    fn from(err: ScanError) -> Self {
+        match err {
+            ScanError::Incomplete(msg) | ScanError::Invalid(msg) => msg,
+        }
+    }
 }
 
-fn read_to_close(close_ch: char, str: &mut impl Iterator<Item = char>, out_buf: &mut String) -> Result<()> {
+pub const KEYWORDS: [&str; 16] = [
+    "and", "class", "else", "false", "for", "fun", "if", "nil",
+    "or", "print", "return", "super", "this", "true", "var", "while",
+];
+
+// copy the rest of a string in embedded code, till the close quote
+fn copy_string(str: &mut Peekable<Chars>, out_buf: &mut String) -> std::result::Result<(), ScanError> {
     while let Some(ch) = str.next() {
-        if ch == close_ch {
-            return Ok(());
+        out_buf.push(ch);
+        match ch {
+            '"' => return Ok(()),
+            // copied with the `$', so the string reads it again as an escape
+            '\\' if str.peek() == Some(&'$') => out_buf.extend(str.next()),
+            '$' if str.peek() == Some(&'{') => {
+                out_buf.push('{');
+                str.next();
+                read_embedded(str, out_buf)?;
+                out_buf.push('}');
+            },
+            _ => {},
+        }
+    }
+    Err(ScanError::Incomplete(dbg_format!("end without close mark: \"")))
+}
+
+// read the code after `${' till the matching `}', which is consumed but not kept
+fn read_embedded(str: &mut Peekable<Chars>, out_buf: &mut String) -> std::result::Result<(), ScanError> {
+    let mut depth = 0;
+    while let Some(ch) = str.next() {
+        match ch {
+            '}' if depth == 0 => return Ok(()),
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            '"' => {
+                out_buf.push(ch);
+                copy_string(str, out_buf)?;
+                continue;
+            },
+            _ => {},
         }
         out_buf.push(ch);
     }
+    Err(ScanError::Incomplete(dbg_format!("end without close mark: }")))
+}
+
+/**
+ * whether a `++' or `--' at the iterator applies to a variable, it must follow
+ * one or be followed by one, otherwise it is read as two operators so that
+ * `1--1' and `-(-x)' keep their meaning
+ *
+ * rest: the code after the first `+' or `-'
+ */
+fn step_operand(list: &[Token], rest: &Peekable<Chars>) -> bool {
+    if let Some(Token::Identifier(_)) = list.last() {
+        return true;
+    }
+    let mut rest = rest.clone().skip(1).skip_while(|ch| ch.is_whitespace());
+    matches!(rest.next(), Some('a'..='z' | 'A'..='Z' | '_'))
+}
+
+/**
+ * read a string after the open quote,
+ * a plain string if there is no `${expr}' in it.
+ * `\$' is a plain `$', so `\${' is kept as it is
+ */
+fn read_string(str: &mut Peekable<Chars>, mode: DecimalMode) -> std::result::Result<Token, ScanError> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut column = 0;
+
+    while let Some(ch) = str.next() {
+        column += 1;
+        match ch {
+            '"' => {
+                if parts.is_empty() {
+                    return Ok(Token::new_string(text));
+                }
+                if !text.is_empty() {
+                    parts.push(StrPart::Text(text.to_share()));
+                }
+                return Ok(Token::Interp(Crc::new(parts)));
+            },
+            '\\' if str.peek() == Some(&'$') => {
+                str.next();
+                column += 1;
+                text.push('$');
+            },
+            '$' if str.peek() == Some(&'{') => {
+                str.next();
+                let mut source = String::new();
+                read_embedded(str, &mut source)?;
+
+                let mut tokens = Vec::new();
+                let expr = scan_from_string_mode(&source, &mut tokens, mode)
+                    .map_err(String::from)
+                    .and_then(|_| Expr::embedded(&Tokens::new(&tokens)))
+                    .map_err(|err| StrPart::error(&source, column, &err))?;
+                if !text.is_empty() {
+                    parts.push(StrPart::Text(std::mem::take(&mut text).to_share()));
+                }
+                let width = source.chars().count() + 2;     // `{' and `}'
+                parts.push(StrPart::Embed { expr: Crc::new(expr), source: source.to_share(), column });
+                column += width;
+            },
+            _ => text.push(ch),
+        }
+    }
+    Err(ScanError::Incomplete(dbg_format!("end without close mark: \"")))
+}
+
+// push the identifier or keyword in the buffer
+fn word_flush(buf: &mut String, list: &mut Vec<Token>) {
+    if buf.is_empty() {
+        return;
+    }
+    let label_new: Crc<str> = buf.clone().to_share();
+    // check keywords before treat it as an identifier
+    list.push(match label_new.as_ref() {
+        "and" => Token::And,
+        "class" => Token::Class,
+        "else" => Token::Else,
+        "false" => Token::False,
+        "for" => Token::For,
+        "fun" => Token::Fun,
+        "if" => Token::If,
+        "nil" => Token::Nil,
+        "or" => Token::Or,
+        "print" => Token::Print,
+        "return" => Token::Return,
+        "super" => Token::Super,
+        "this" => Token::This,
+        "true" => Token::True,
+        "var" => Token::Var,
+        "while" => Token::While,
+        _ => Token::Identifier(label_new),
+    });
+    buf.clear();
+}
+
+/**
+ * read a number literal started with the digit `first',
+ * the literal is checked by `Number::from_mode'
+ */
+fn read_number(first: char, str: &mut Peekable<Chars>, mode: DecimalMode) -> Result<Number> {
+    let mut literal = String::from(first);
+    let radix = first == '0' && str.peek().is_some_and(|ch| matches!(ch, 'x' | 'X' | 'b' | 'B' | 'o' | 'O'));
+
+    while let Some(&ch) = str.peek() {
+        let take = match ch {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => true,
+            // a dot not followed by digit is not a part of the number
+            '.' => !radix && {
+                let mut ahead = str.clone();
+                ahead.next();
+                ahead.peek().is_some_and(char::is_ascii_digit)
+            },
+            // sign of the exponent
+            '+' | '-' => !radix && literal.ends_with(['e', 'E']),
+            _ => false,
+        };
+        if !take {
+            break;
+        }
+        literal.push(ch);
+        str.next();
+    }
+    Number::from_mode(&literal, mode)
+}
 
-    return Err(dbg_format!("end without close mark: {}", close_ch));
+#[allow(dead_code)]
+pub fn scan_from_string(line: &str, list: &mut Vec<Token>) -> std::result::Result<(), ScanError> {
+    scan_from_string_mode(line, list, DecimalMode::Float)
 }
 
-pub fn scan_from_string(line: &str, list: &mut Vec<Token>) -> Result<()> {
+/**
+ * scan the tokens of the line
+ *
+ * mode: type of the number literals with a `.' or an exponent
+ */
+pub fn scan_from_string_mode(line: &str, list: &mut Vec<Token>, mode: DecimalMode) -> std::result::Result<(), ScanError> {
 
-    let mut parse_type = ParseType::Identifier;
     let mut string_buffer: String = String::new();
     let mut line_itr = line.chars().peekable();
 
@@ -148,101 +458,74 @@ pub fn scan_from_string(line: &str, list: &mut Vec<Token>) -> Result<()> {
 
     while let Some(ch) = line_itr.next() {
 
-        // FIXME: demical cannot be parsed now
-        if matches!(parse_type, ParseType::Number) && ch == '.' {
-            buf.push(ch);
+        if buf.is_empty() && ch.is_ascii_digit() {
+            list.push(Token::Number(read_number(ch, &mut line_itr, mode)?));
             continue;
         }
 
         if matches!(ch, 'a'..='z' | 'A'..='Z' | '0'..='9' | '_') {
-            match ch {
-                '0'..='9' => {
-                    if buf.is_empty() && matches!(parse_type, ParseType::Identifier) {
-                        parse_type = ParseType::Number;
-                    }
-                    buf.push(ch);
-                },
-                _ => {
-                    if let ParseType::Number = parse_type {
-                        return Err(dbg_format!("got unexpected char during parsing the number"));
-                    }
-                    buf.push(ch);
-                },
-            }
+            buf.push(ch);
             continue;
         }
 
-        if !buf.is_empty() {
-            let label_new: Crc<str> = buf.clone().to_share();
-            list.push(match parse_type {
-                ParseType::Number => {
-                    if label_new.ends_with('.') {
-                        return Err(dbg_format!("wrong number string get: {}", label_new));
-                    }
-                    match Number::from(label_new.as_ref()) {
-                        Ok(num) => Token::Number(num),
-                        Err(ex) => return Err(ex),
-                    }
-                },
-                ParseType::Identifier => {
-                    // check keywords before treat it as an identifier
-                    match label_new.as_ref() {
-                        "and" => Token::And,
-                        "class" => Token::Class,
-                        "else" => Token::Else,
-                        "false" => Token::False,
-                        "for" => Token::For,
-                        "fun" => Token::Fun,
-                        "if" => Token::If,
-                        "nil" => Token::Nil,
-                        "or" => Token::Or,
-                        "print" => Token::Print,
-                        "return" => Token::Return,
-                        "super" => Token::Super,
-                        "this" => Token::This,
-                        "true" => Token::True,
-                        "var" => Token::Var,
-                        "while" => Token::While,
-                        _ => Token::Identifier(label_new),
-                    }
-                },
-            });
-            parse_type = ParseType::Identifier;
-            buf.clear();
-        }
+        word_flush(buf, list);
 
-        let peeked = line_itr.peek();
+        let peeked = line_itr.peek().copied();
         match (ch, peeked) {
             // None is not avaiable
-            ('"', Some(_)) => {
-                read_to_close(ch, &mut line_itr, buf)?;
-                list.push(Token::new_string(buf.clone()));
-                buf.clear();
-            },
+            ('"', Some(_)) => list.push(read_string(&mut line_itr, mode)?),
             ('(', _) => list.push(Token::LeftParen),
             (')', _) => list.push(Token::RightParen),
             ('{', _) => list.push(Token::LeftBrace),
             ('}', _) => list.push(Token::RightBrace),
+            ('[', _) => list.push(Token::LeftBracket),
+            (']', _) => list.push(Token::RightBracket),
+            (':', _) => list.push(Token::Colon),
+            ('?', Some('?')) => {
+                list.push(Token::QuestionQuestion);
+                line_itr.next();
+            },
+            ('?', _) => list.push(Token::Question),
 
             (',', _) => list.push(Token::Comma),
             ('.', _) => list.push(Token::Dot),
             (';', _) => list.push(Token::Semicolon),
 
-            (ch, Some('=')) => {
-                let mut used = true;
-                match ch {
-                    '!' => list.push(Token::BangEqual),
-                    '=' => list.push(Token::EqualEqual),
-                    '<' => list.push(Token::LessEqual),
-                    '>' => list.push(Token::GreaterEqual),
-                    _ => used = false,
-                }
-                if used { line_itr.next(); }
+            (ch @ ('!' | '=' | '<' | '>' | '+' | '-' | '*' | '/' | '%'), Some('=')) => {
+                list.push(match ch {
+                    '!' => Token::BangEqual,
+                    '=' => Token::EqualEqual,
+                    '<' => Token::LessEqual,
+                    '>' => Token::GreaterEqual,
+                    '+' => Token::PlusEqual,
+                    '-' => Token::MinusEqual,
+                    '*' => Token::StarEqual,
+                    '/' => Token::SlashEqual,
+                    _ => Token::PercentEqual,
+                });
+                line_itr.next();
+            },
+            ('+', Some('+')) if step_operand(list, &line_itr) => {
+                list.push(Token::PlusPlus);
+                line_itr.next();
+            },
+            ('-', Some('-')) if step_operand(list, &line_itr) => {
+                list.push(Token::MinusMinus);
+                line_itr.next();
             },
 
             ('+', _) => list.push(Token::Plus),
             ('-', _) => list.push(Token::Minus),
             ('*', _) => list.push(Token::Star),
+            ('%', _) => list.push(Token::Percent),
+            ('/', Some('/')) => {
+                // comment, skip to the end of line
+                for ch in line_itr.by_ref() {
+                    if ch == '\n' {
+                        break;
+                    }
+                }
+            },
             ('/', _) => list.push(Token::Slash),
 
             ('!', _) => list.push(Token::Bang),
@@ -255,10 +538,11 @@ pub fn scan_from_string(line: &str, list: &mut Vec<Token>) -> Result<()> {
             }
 
             (_, _) => {
-                return Err(dbg_format!("encounter unepxected char: {}, next: {:#?}", ch, peeked));
+                return Err(ScanError::Invalid(dbg_format!("encounter unepxected char: {}, next: {:#?}", ch, peeked)));
             },
         };
     }
+    word_flush(buf, list);
 
     return Ok(());
 }
@@ -4,6 +4,8 @@ pub type Result<T> = std::result::Result<T, String>;
 
 pub type Crc<T> = std::rc::Rc<T>;
 
+pub type Weak<T> = std::rc::Weak<T>;
+
 
 #[macro_export]
 macro_rules! dbg_format {
@@ -0,0 +1,384 @@
+use std::collections::{BTreeMap, HashMap};
+
+use num_bigint::BigInt;
+use rust_decimal::Decimal as ExactDecimal;
+
+use crate::{dbg_format, vm::native::NativeFnPtr};
+
+use super::{
+    common::{Crc, Result},
+    number::Number,
+    object::{Object, ObjectRc},
+    shared_str::{SharedStr, SharedStrExt},
+    value::Value,
+};
+
+
+/**
+ * rust values that can be converted to lox objects
+ */
+pub trait IntoLox {
+    fn into_lox(self) -> ObjectRc;
+}
+
+/**
+ * rust values that can be converted from lox objects,
+ * fails if the object has another type
+ */
+pub trait FromLox: Sized {
+    fn from_lox(obj: &ObjectRc) -> Result<Self>;
+}
+
+/**
+ * convert the argument `idx' of the native function `name'
+ */
+pub fn native_arg<T: FromLox>(name: &str, args: &[ObjectRc], idx: usize) -> Result<T> {
+    match args.get(idx) {
+        Some(arg) => T::from_lox(arg).map_err(|err| format!("argument {} of `{}`: {}", idx + 1, name, err)),
+        None => Err(dbg_format!("function `{}` got too few arguments", name)),
+    }
+}
+
+fn type_mismatch<T>(expect: &str, obj: &ObjectRc) -> Result<T> {
+    Err(dbg_format!("type mismatch: expect {}, got {}", expect, obj.type_name()))
+}
+
+impl IntoLox for ObjectRc {
+    fn into_lox(self) -> ObjectRc {
+        self
+    }
+}
+
+impl FromLox for ObjectRc {
+    fn from_lox(obj: &ObjectRc) -> Result<Self> {
+        Ok(obj.clone())
+    }
+}
+
+impl IntoLox for Value {
+    fn into_lox(self) -> ObjectRc {
+        self.to_rc()
+    }
+}
+
+impl FromLox for Value {
+    fn from_lox(obj: &ObjectRc) -> Result<Self> {
+        Ok(Value::from_rc(obj.clone()))
+    }
+}
+
+impl IntoLox for () {
+    fn into_lox(self) -> ObjectRc {
+        Object::Nil.to_rc()
+    }
+}
+
+impl FromLox for () {
+    fn from_lox(obj: &ObjectRc) -> Result<Self> {
+        match obj.as_ref() {
+            Object::Nil => Ok(()),
+            _ => type_mismatch("Nil", obj),
+        }
+    }
+}
+
+impl IntoLox for Number {
+    fn into_lox(self) -> ObjectRc {
+        Object::Number(self).to_rc()
+    }
+}
+
+impl FromLox for Number {
+    fn from_lox(obj: &ObjectRc) -> Result<Self> {
+        match obj.as_ref() {
+            Object::Number(num) => Ok(num.clone()),
+            _ => type_mismatch("Number", obj),
+        }
+    }
+}
+
+impl IntoLox for bool {
+    fn into_lox(self) -> ObjectRc {
+        Object::Boolean(self).to_rc()
+    }
+}
+
+impl FromLox for bool {
+    fn from_lox(obj: &ObjectRc) -> Result<Self> {
+        match obj.as_ref() {
+            Object::Boolean(b) => Ok(*b),
+            _ => type_mismatch("Boolean", obj),
+        }
+    }
+}
+
+macro_rules! convert_integer {
+    ($($ty:ty),*) => {$(
+        impl IntoLox for $ty {
+            fn into_lox(self) -> ObjectRc {
+                match i64::try_from(self) {
+                    Ok(ii) => Object::Number(Number::Integer(ii)).to_rc(),
+                    Err(_) => Object::Number(Number::Big(BigInt::from(self))).to_rc(),
+                }
+            }
+        }
+
+        impl FromLox for $ty {
+            fn from_lox(obj: &ObjectRc) -> Result<Self> {
+                match obj.as_ref() {
+                    Object::Number(Number::Integer(ii)) => <$ty>::try_from(*ii)
+                        .map_err(|_| dbg_format!("integer {} out of range for {}", ii, stringify!($ty))),
+                    Object::Number(Number::Big(big)) => <$ty>::try_from(big)
+                        .map_err(|_| dbg_format!("integer {} out of range for {}", big, stringify!($ty))),
+                    _ => type_mismatch("Integer", obj),
+                }
+            }
+        }
+    )*};
+}
+
+convert_integer!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+macro_rules! convert_float {
+    ($($ty:ty),*) => {$(
+        impl IntoLox for $ty {
+            fn into_lox(self) -> ObjectRc {
+                Object::Number(Number::Decimal(self as f64)).to_rc()
+            }
+        }
+
+        // integers are accepted as well
+        impl FromLox for $ty {
+            fn from_lox(obj: &ObjectRc) -> Result<Self> {
+                match obj.as_ref() {
+                    Object::Number(num) => Ok(num.to_f64() as $ty),
+                    _ => type_mismatch("Number", obj),
+                }
+            }
+        }
+    )*};
+}
+
+convert_float!(f32, f64);
+
+impl IntoLox for BigInt {
+    fn into_lox(self) -> ObjectRc {
+        Object::Number(Number::from_big(self)).to_rc()
+    }
+}
+
+impl FromLox for BigInt {
+    fn from_lox(obj: &ObjectRc) -> Result<Self> {
+        match obj.as_ref() {
+            Object::Number(Number::Integer(ii)) => Ok(BigInt::from(*ii)),
+            Object::Number(Number::Big(big)) => Ok(big.clone()),
+            _ => type_mismatch("Integer", obj),
+        }
+    }
+}
+
+impl IntoLox for ExactDecimal {
+    fn into_lox(self) -> ObjectRc {
+        Object::Number(Number::Exact(self)).to_rc()
+    }
+}
+
+// integers are accepted as well
+impl FromLox for ExactDecimal {
+    fn from_lox(obj: &ObjectRc) -> Result<Self> {
+        match obj.as_ref() {
+            Object::Number(num @ (Number::Exact(_) | Number::Integer(_) | Number::Big(_))) => num.to_exact()
+                .ok_or_else(|| dbg_format!("integer {} out of range for exact decimal", num)),
+            _ => type_mismatch("Exact", obj),
+        }
+    }
+}
+
+impl IntoLox for &str {
+    fn into_lox(self) -> ObjectRc {
+        Object::new_string(self.to_string()).to_rc()
+    }
+}
+
+impl IntoLox for String {
+    fn into_lox(self) -> ObjectRc {
+        Object::new_string(self).to_rc()
+    }
+}
+
+impl FromLox for String {
+    fn from_lox(obj: &ObjectRc) -> Result<Self> {
+        match obj.as_ref() {
+            Object::String(str) => Ok(str.to_string()),
+            _ => type_mismatch("String", obj),
+        }
+    }
+}
+
+impl IntoLox for SharedStr {
+    fn into_lox(self) -> ObjectRc {
+        Object::String(self).to_rc()
+    }
+}
+
+impl FromLox for SharedStr {
+    fn from_lox(obj: &ObjectRc) -> Result<Self> {
+        match obj.as_ref() {
+            Object::String(str) => Ok(str.clone()),
+            _ => type_mismatch("String", obj),
+        }
+    }
+}
+
+// `None' is nil
+impl<T: IntoLox> IntoLox for Option<T> {
+    fn into_lox(self) -> ObjectRc {
+        match self {
+            Some(val) => val.into_lox(),
+            None => Object::Nil.to_rc(),
+        }
+    }
+}
+
+impl<T: FromLox> FromLox for Option<T> {
+    fn from_lox(obj: &ObjectRc) -> Result<Self> {
+        match obj.as_ref() {
+            Object::Nil => Ok(None),
+            _ => Ok(Some(T::from_lox(obj)?)),
+        }
+    }
+}
+
+impl<T: IntoLox> IntoLox for Vec<T> {
+    fn into_lox(self) -> ObjectRc {
+        Object::new_list(self.into_iter().map(T::into_lox).collect()).to_rc()
+    }
+}
+
+impl<T: FromLox> FromLox for Vec<T> {
+    fn from_lox(obj: &ObjectRc) -> Result<Self> {
+        match obj.as_ref() {
+            Object::List(list) => list.borrow().iter()
+                .enumerate()
+                .map(|(idx, item)| T::from_lox(item).map_err(|err| format!("{} (at index {})", err, idx)))
+                .collect(),
+            _ => type_mismatch("List", obj),
+        }
+    }
+}
+
+impl<T: IntoLox> IntoLox for HashMap<String, T> {
+    fn into_lox(self) -> ObjectRc {
+        let map: BTreeMap<SharedStr, ObjectRc> = self.into_iter()
+            .map(|(key, val)| (key.to_share(), val.into_lox()))
+            .collect();
+        Object::new_map(map).to_rc()
+    }
+}
+
+impl<T: FromLox> FromLox for HashMap<String, T> {
+    fn from_lox(obj: &ObjectRc) -> Result<Self> {
+        match obj.as_ref() {
+            Object::Map(map) => map.borrow().iter()
+                .map(|(key, item)| match T::from_lox(item) {
+                    Ok(val) => Ok((key.to_string(), val)),
+                    Err(err) => Err(format!("{} (at key \"{}\")", err, key)),
+                })
+                .collect(),
+            _ => type_mismatch("Map", obj),
+        }
+    }
+}
+
+/**
+ * values a typed native function can return,
+ * `Err' of a `Result' is raised as a runtime error
+ */
+pub trait NativeReturn {
+    fn into_return(self) -> Result<ObjectRc>;
+}
+
+impl<T: IntoLox> NativeReturn for Result<T> {
+    fn into_return(self) -> Result<ObjectRc> {
+        self.map(T::into_lox)
+    }
+}
+
+macro_rules! native_return {
+    ($($ty:ty),*) => {$(
+        impl NativeReturn for $ty {
+            fn into_return(self) -> Result<ObjectRc> {
+                Ok(self.into_lox())
+            }
+        }
+    )*};
+}
+
+native_return!(
+    ObjectRc, Value, (), bool, String, &str, SharedStr, BigInt, ExactDecimal, Number,
+    i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64
+);
+
+impl<T: IntoLox> NativeReturn for Option<T> {
+    fn into_return(self) -> Result<ObjectRc> {
+        Ok(self.into_lox())
+    }
+}
+
+impl<T: IntoLox> NativeReturn for Vec<T> {
+    fn into_return(self) -> Result<ObjectRc> {
+        Ok(self.into_lox())
+    }
+}
+
+impl<T: IntoLox> NativeReturn for HashMap<String, T> {
+    fn into_return(self) -> Result<ObjectRc> {
+        Ok(self.into_lox())
+    }
+}
+
+/**
+ * rust functions that can be registered as natives directly,
+ * such as `fn(i64, String) -> bool'
+ *
+ * Args: tuple of the argument types
+ */
+pub trait IntoNative<Args> {
+    fn arity() -> usize;
+    fn into_native(self, name: &str) -> NativeFnPtr;
+}
+
+macro_rules! into_native {
+    ($count:expr; $($arg:ident),*) => {
+        #[allow(non_snake_case, unused_mut, unused_variables)]
+        impl<Func, Ret, $($arg),*> IntoNative<($($arg,)*)> for Func
+        where
+            Func: Fn($($arg),*) -> Ret + 'static,
+            Ret: NativeReturn,
+            $($arg: FromLox,)*
+        {
+            fn arity() -> usize {
+                $count
+            }
+
+            fn into_native(self, name: &str) -> NativeFnPtr {
+                let name = name.to_string();
+                Crc::new(move |_vm, args| {
+                    let mut idx = 0..;
+                    $(
+                        let $arg = native_arg::<$arg>(&name, args, idx.next().unwrap_or_default())?;
+                    )*
+                    self($($arg),*).into_return()
+                })
+            }
+        }
+    };
+}
+
+into_native!(0;);
+into_native!(1; A1);
+into_native!(2; A1, A2);
+into_native!(3; A1, A2, A3);
+into_native!(4; A1, A2, A3, A4);
+into_native!(5; A1, A2, A3, A4, A5);
+into_native!(6; A1, A2, A3, A4, A5, A6);
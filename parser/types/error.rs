@@ -0,0 +1,36 @@
+use std::fmt::Display;
+
+use crate::vm::limits::LimitExceeded;
+
+
+/**
+ * errors reported to the host by the embedding api
+ *
+ * Undefined: no global with the name
+ * NotCallable: the global is not a function
+ * Arity: wrong number of arguments for the function
+ * Limit: the execution budget is exceeded, with the message
+ * Runtime: the function failed, with the message
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoxError {
+    Undefined(String),
+    NotCallable(String),
+    Arity { name: String, expected: usize, got: usize },
+    Limit(LimitExceeded, String),
+    Runtime(String),
+}
+
+impl Display for LoxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Undefined(name) => write!(f, "undefined global: {}", name),
+            Self::NotCallable(name) => write!(f, "not a function: {}", name),
+            Self::Arity { name, expected, got } => write!(f, "function `{}` expect {} arguments, got {}", name, expected, got),
+            Self::Limit(_, msg) => write!(f, "{}", msg),
+            Self::Runtime(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for LoxError {}
@@ -0,0 +1,25 @@
+use crate::syntax::statement::Stmt;
+
+use super::{common::Crc, shared_str::SharedStr};
+
+
+/**
+ * a function declared in lox code
+ *
+ * it is created once while parsing the declaration, executing the
+ * declaration or passing the function around only shares the Rc
+ */
+#[derive(Debug)]
+pub struct LoxFunction {
+    pub name: SharedStr,
+    pub params: Vec<SharedStr>,
+    pub body: Stmt,
+}
+
+pub type LoxFunctionRc = Crc<LoxFunction>;
+
+impl LoxFunction {
+    pub fn new(name: SharedStr, params: Vec<SharedStr>, body: Stmt) -> Self {
+        Self { name, params, body }
+    }
+}
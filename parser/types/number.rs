@@ -1,38 +1,197 @@
 
+/**
+ * what happens when the result of integer arithmetic doesn't fit in i64
+ *
+ * Error: a runtime error
+ * Decimal: the result is computed as a decimal instead
+ * BigInt: the result is promoted to a big integer, the default
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowMode {
+    Error,
+    Decimal,
+    #[default]
+    BigInt,
+}
+
+impl Display for OverflowMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Decimal => write!(f, "decimal"),
+            Self::BigInt => write!(f, "bigint"),
+        }
+    }
+}
+
+impl FromStr for OverflowMode {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self> {
+        match str {
+            "error" => Ok(Self::Error),
+            "decimal" => Ok(Self::Decimal),
+            "bigint" => Ok(Self::BigInt),
+            _ => Err(dbg_format!("unknown overflow mode: {}", str)),
+        }
+    }
+}
+
+/**
+ * the type of number literals with a `.' or an exponent
+ *
+ * Float: f64, `Decimal'
+ * Exact: exact decimal with 28 significant digits, `Exact'
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DecimalMode {
+    #[default]
+    Float,
+    Exact,
+}
+
+impl Display for DecimalMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Float => write!(f, "float"),
+            Self::Exact => write!(f, "exact"),
+        }
+    }
+}
+
+impl FromStr for DecimalMode {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self> {
+        match str {
+            "float" => Ok(Self::Float),
+            "exact" => Ok(Self::Exact),
+            _ => Err(dbg_format!("unknown decimal mode: {}", str)),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Number {
     Integer(i64),
     Decimal(f64),
+    // only for integers beyond i64, see `from_big'
+    Big(BigInt),
+    Exact(ExactDecimal),
 }
 
-use std::{cmp::Ordering, fmt::Display, ops::{self, Neg}};
+use std::{cmp::Ordering, fmt::Display, hash::{Hash, Hasher}, ops::{self, Neg}, str::FromStr};
+
+use num_bigint::{BigInt, Sign};
+use num_traits::{FromPrimitive, ToPrimitive};
+use rust_decimal::Decimal as ExactDecimal;
 
 use Number::{*};
 
 use crate::dbg_format;
 use crate::types::common::Result;
 
+// largest result of `pow' on big integers, in bits, so a script cannot
+// stall the host computing a huge power
+pub const BIG_POW_BITS_MAX: u64 = 1 << 20;
+
 impl Number {
+
+    /**
+     * parse a number literal, such as `42', `1_000', `0.5', `1e-3',
+     * `0xFF', `0b1010' and `0o17'
+     */
     pub fn from(str: &str) -> Result<Number> {
-        if str.contains('.') {
-            match str.parse::<f64>() {
-                Err(ex) => Err(dbg_format!("{}", ex)),
-                Ok(d) => Ok(Number::Decimal(d)),
+        Self::from_mode(str, DecimalMode::Float)
+    }
+
+    /**
+     * parse a number literal, decimals are parsed as `mode'
+     */
+    pub fn from_mode(str: &str, mode: DecimalMode) -> Result<Number> {
+        let (radix, body) = match str.get(..2) {
+            Some("0x" | "0X") => (16, &str[2..]),
+            Some("0b" | "0B") => (2, &str[2..]),
+            Some("0o" | "0O") => (8, &str[2..]),
+            _ => (10, str),
+        };
+        let digits = Self::digits_strip(str, radix)?;
+        if radix != 10 {
+            let digits = &digits[2..];
+            if body.is_empty() {
+                return Err(dbg_format!("missing digits in number literal `{}`", str));
+            }
+            if let Some(ch) = digits.chars().find(|ch| !ch.is_digit(radix)) {
+                return Err(dbg_format!("invalid digit `{}` for base {} in number literal `{}`", ch, radix, str));
+            }
+            return match i64::from_str_radix(digits, radix) {
+                Ok(ii) => Ok(Integer(ii)),
+                Err(_) => Err(dbg_format!("integer literal out of range: `{}`", str)),
+            };
+        }
+
+        if let Some(ch) = digits.chars().find(|ch| !matches!(ch, '0'..='9' | '.' | 'e' | 'E' | '+' | '-')) {
+            return Err(dbg_format!("invalid character `{}` in number literal `{}`", ch, str));
+        }
+        if digits.contains(['.', 'e', 'E']) && mode == DecimalMode::Exact {
+            let exact = match digits.contains(['e', 'E']) {
+                true => ExactDecimal::from_scientific(&digits),
+                false => ExactDecimal::from_str(&digits),
+            };
+            return match exact {
+                Ok(exact) => Ok(Exact(exact)),
+                Err(_) if digits.parse::<f64>().is_ok() => Err(dbg_format!("number literal out of range: `{}`", str)),
+                Err(_) => Err(dbg_format!("malformed number literal `{}`", str)),
+            };
+        }
+        if digits.contains(['.', 'e', 'E']) {
+            match digits.parse::<f64>() {
+                Ok(ff) if ff.is_finite() => Ok(Decimal(ff)),
+                Ok(_) => Err(dbg_format!("number literal out of range: `{}`", str)),
+                Err(_) => Err(dbg_format!("malformed number literal `{}`", str)),
             }
         } else {
-            match str.parse::<i64>() {
-                Err(ex) => Err(dbg_format!("{}", ex)),
-                Ok(d) => Ok(Number::Integer(d)),
+            match digits.parse::<i64>() {
+                Ok(ii) => Ok(Integer(ii)),
+                Err(_) => Err(dbg_format!("integer literal out of range: `{}`", str)),
             }
         }
     }
+
+    // remove the digit separators, which are allowed only between two digits
+    fn digits_strip(str: &str, radix: u32) -> Result<String> {
+        let chars: Vec<char> = str.chars().collect();
+        for (idx, ch) in chars.iter().enumerate() {
+            if *ch != '_' {
+                continue;
+            }
+            let is_digit = |idx: Option<usize>| idx
+                .and_then(|idx| chars.get(idx))
+                .is_some_and(|ch| ch.is_digit(radix));
+            if !is_digit(idx.checked_sub(1)) || !is_digit(Some(idx + 1)) {
+                return Err(dbg_format!("misplaced `_` in number literal `{}`", str));
+            }
+        }
+        Ok(str.replace('_', ""))
+    }
 }
 
+/**
+ * integers are printed as is, floats always have a `.' or an exponent
+ * in the shortest form that parses back to the same value, such as
+ * `1.0', `0.1' and `1e100', or `nan', `inf' and `-inf'.
+ * exact decimals keep their scale, such as `1.50'
+ */
 impl Display for Number {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Integer(ii) => write!(f, "{}", ii),
-            Decimal(ii) => write!(f, "{}", ii),
+            Decimal(ff) if ff.is_nan() => write!(f, "nan"),
+            Decimal(ff) if ff.is_infinite() => write!(f, "{}inf", if *ff < 0.0 { "-" } else { "" }),
+            // `Debug' of f64 is the shortest round-trip form
+            Decimal(ff) => write!(f, "{:?}", ff),
+            Big(big) => write!(f, "{}", big),
+            Exact(exact) => write!(f, "{}", exact),
         }
     }
 }
@@ -42,6 +201,8 @@ impl Clone for Number {
         match self {
             Self::Integer(arg0) => Self::Integer(arg0.clone()),
             Self::Decimal(arg0) => Self::Decimal(arg0.clone()),
+            Self::Big(arg0) => Self::Big(arg0.clone()),
+            Self::Exact(arg0) => Self::Exact(*arg0),
         }
     }
 }
@@ -49,10 +210,11 @@ impl Clone for Number {
 impl Neg for Number {
     type Output = Number;
 
+    // cannot fail, `-i64::MIN' becomes a big integer
     fn neg(self) -> Self::Output {
-        match self {
-            Integer(ii) => Integer(-ii),
-            Decimal(ff) => Decimal(-ff),
+        match self.neg_ref(OverflowMode::BigInt) {
+            Ok(num) => num,
+            Err(_) => unreachable!("negation never fails in BigInt mode"),
         }
     }
 }
@@ -60,109 +222,309 @@ impl Neg for Number {
 impl ops::Add<Number> for Number {
     type Output = Result<Number>;
     fn add(self, rhs: Number) -> Self::Output {
-        self.add_ref(&rhs)
+        self.add_ref(&rhs, OverflowMode::default())
     }
 }
 
 impl ops::Sub<Number> for Number {
     type Output = Result<Number>;
     fn sub(self, rhs: Number) -> Self::Output {
-        self.sub_ref(&rhs)
+        self.sub_ref(&rhs, OverflowMode::default())
     }
 }
 
 impl ops::Mul<Number> for Number {
     type Output = Result<Number>;
     fn mul(self, rhs: Number) -> Self::Output {
-        self.mul_ref(&rhs)
+        self.mul_ref(&rhs, OverflowMode::default())
     }
 }
 
 impl ops::Div<Number> for Number {
     type Output = Result<Number>;
     fn div(self, rhs: Number) -> Self::Output {
-        self.div_ref(&rhs)
+        self.div_ref(&rhs, OverflowMode::default())
     }
 }
 
 impl Number {
 
+    /**
+     * the integer as `Integer' if it fits in i64, `Big' otherwise,
+     * so every integer has only one representation
+     */
+    pub fn from_big(big: BigInt) -> Number {
+        match big.to_i64() {
+            Some(ii) => Integer(ii),
+            None => Big(big),
+        }
+    }
+
     pub fn is_zero(&self) -> bool {
         match self {
             Integer(0) => true,
             Decimal(0.0) => true,
+            Exact(exact) => exact.is_zero(),
             _ => false,
         }
     }
 
-    pub fn add_ref(&self, rhs: &Self) -> Result<Number> {
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Integer(ii) => *ii as f64,
+            Decimal(ff) => *ff,
+            Big(big) => big.to_f64().unwrap_or(f64::NAN),
+            Exact(exact) => exact.normalize().to_f64().unwrap_or(f64::NAN),
+        }
+    }
+
+    // None for decimals
+    fn to_big(&self) -> Option<BigInt> {
+        match self {
+            Integer(ii) => Some(BigInt::from(*ii)),
+            Big(big) => Some(big.clone()),
+            Decimal(_) | Exact(_) => None,
+        }
+    }
+
+    /**
+     * convert to an exact decimal, floats are converted by their
+     * shortest representation, so `0.1' stays `0.1'
+     */
+    pub fn to_exact(&self) -> Option<ExactDecimal> {
+        match self {
+            Integer(ii) => Some(ExactDecimal::from(*ii)),
+            Decimal(ff) => ExactDecimal::from_str(&ff.to_string())
+                .ok()
+                .or_else(|| ExactDecimal::from_f64(*ff)),
+            Big(big) => ExactDecimal::from_str(&big.to_string()).ok(),
+            Exact(exact) => Some(*exact),
+        }
+    }
+
+    // both are exact decimals or integers, and at least one is exact
+    fn is_exact_pair(&self, rhs: &Self) -> bool {
+        matches!((self, rhs), (Exact(_), Exact(_) | Integer(_) | Big(_)) | (Integer(_) | Big(_), Exact(_)))
+    }
+
+    // operation on exact decimals, falls back to f64 if a big integer
+    // doesn't fit in the exact decimal
+    fn exact_op(lhs: &Self, rhs: &Self, sym: &str,
+        op: fn(ExactDecimal, ExactDecimal) -> Option<ExactDecimal>, op_float: fn(f64, f64) -> f64) -> Result<Number> {
+        match (lhs.to_exact(), rhs.to_exact()) {
+            (Some(ii), Some(jj)) => match op(ii, jj) {
+                Some(ret) => Ok(Exact(ret)),
+                None => Err(dbg_format!("decimal overflow: {} {} {}", lhs, sym, rhs)),
+            },
+            _ => Ok(Decimal(op_float(lhs.to_f64(), rhs.to_f64()))),
+        }
+    }
+
+    pub fn add_ref(&self, rhs: &Self, mode: OverflowMode) -> Result<Number> {
         match (self, rhs) {
             (Integer(ii), Integer(jj)) => {
-                Ok(Integer(*ii + *jj))
+                Self::int_op(*ii, *jj, mode, "+", i64::checked_add, |ii, jj| ii + jj)
             },
-            (Decimal(ii), Decimal(jj)) => {
-                Ok(Decimal(*ii + *jj))
+            _ if self.is_exact_pair(rhs) => {
+                Self::exact_op(self, rhs, "+", ExactDecimal::checked_add, |ii, jj| ii + jj)
             },
-            (Integer(ii), Decimal(jj)) => {
-                Ok(Decimal(*ii as f64 + *jj))
+            (Decimal(_) | Exact(_), _) | (_, Decimal(_) | Exact(_)) => {
+                Ok(Decimal(self.to_f64() + rhs.to_f64()))
             },
-            (Decimal(ii), Integer(jj)) => {
-                Ok(Decimal(*ii + *jj as f64))
-            }
+            _ => Ok(Self::big_op(self, rhs, |ii, jj| ii + jj)),
         }
     }
 
-    pub fn sub_ref(&self, rhs: &Self) -> Result<Number> {
+    pub fn sub_ref(&self, rhs: &Self, mode: OverflowMode) -> Result<Number> {
         match (self, rhs) {
             (Integer(ii), Integer(jj)) => {
-                Ok(Integer(*ii - *jj))
+                Self::int_op(*ii, *jj, mode, "-", i64::checked_sub, |ii, jj| ii - jj)
             },
-            (Decimal(ii), Decimal(jj)) => {
-                Ok(Decimal(*ii - *jj))
+            _ if self.is_exact_pair(rhs) => {
+                Self::exact_op(self, rhs, "-", ExactDecimal::checked_sub, |ii, jj| ii - jj)
             },
-            (Integer(ii), Decimal(jj)) => {
-                Ok(Decimal(*ii as f64 - *jj))
+            (Decimal(_) | Exact(_), _) | (_, Decimal(_) | Exact(_)) => {
+                Ok(Decimal(self.to_f64() - rhs.to_f64()))
             },
-            (Decimal(ii), Integer(jj)) => {
-                Ok(Decimal(*ii - *jj as f64))
-            }
+            _ => Ok(Self::big_op(self, rhs, |ii, jj| ii - jj)),
         }
     }
-    pub fn mul_ref(&self, rhs: &Self) -> Result<Number> {
+
+    pub fn mul_ref(&self, rhs: &Self, mode: OverflowMode) -> Result<Number> {
         match (self, rhs) {
             (Integer(ii), Integer(jj)) => {
-                Ok(Integer(*ii * *jj))
+                Self::int_op(*ii, *jj, mode, "*", i64::checked_mul, |ii, jj| ii * jj)
             },
-            (Decimal(ii), Decimal(jj)) => {
-                Ok(Decimal(*ii * *jj))
+            _ if self.is_exact_pair(rhs) => {
+                Self::exact_op(self, rhs, "*", ExactDecimal::checked_mul, |ii, jj| ii * jj)
             },
-            (Integer(ii), Decimal(jj)) => {
-                Ok(Decimal(*ii as f64 * *jj))
+            (Decimal(_) | Exact(_), _) | (_, Decimal(_) | Exact(_)) => {
+                Ok(Decimal(self.to_f64() * rhs.to_f64()))
             },
-            (Decimal(ii), Integer(jj)) => {
-                Ok(Decimal(*ii * *jj as f64))
-            }
+            _ => Ok(Self::big_op(self, rhs, |ii, jj| ii * jj)),
         }
     }
 
-    pub fn div_ref(&self, rhs: &Self) -> Result<Number> {
+    pub fn div_ref(&self, rhs: &Self, mode: OverflowMode) -> Result<Number> {
         if rhs.is_zero() {
             return Err(dbg_format!("cannot divide by Zero: {} / {}", self, rhs));
         }
         match (self, rhs) {
             (Integer(ii), Integer(jj)) => {
-                Ok(Integer(*ii / *jj))
+                // only `i64::MIN / -1' overflows
+                Self::int_op(*ii, *jj, mode, "/", i64::checked_div, |ii, jj| ii / jj)
+            },
+            _ if self.is_exact_pair(rhs) => {
+                Self::exact_op(self, rhs, "/", ExactDecimal::checked_div, |ii, jj| ii / jj)
+            },
+            (Decimal(_) | Exact(_), _) | (_, Decimal(_) | Exact(_)) => {
+                Ok(Decimal(self.to_f64() / rhs.to_f64()))
+            },
+            _ => Ok(Self::big_op(self, rhs, |ii, jj| ii / jj)),
+        }
+    }
+
+    /**
+     * remainder of the truncated division, has the sign of `self'
+     */
+    pub fn rem_ref(&self, rhs: &Self, mode: OverflowMode) -> Result<Number> {
+        if rhs.is_zero() {
+            return Err(dbg_format!("cannot divide by Zero: {} % {}", self, rhs));
+        }
+        match (self, rhs) {
+            (Integer(ii), Integer(jj)) => {
+                // `i64::MIN % -1' only overflows in the hidden division
+                let rem = |ii: i64, jj: i64| if jj == -1 { Some(0) } else { ii.checked_rem(jj) };
+                Self::int_op(*ii, *jj, mode, "%", rem, |ii, jj| ii % jj)
             },
-            (Decimal(ii), Decimal(jj)) => {
-                Ok(Decimal(*ii / *jj))
+            _ if self.is_exact_pair(rhs) => {
+                Self::exact_op(self, rhs, "%", ExactDecimal::checked_rem, |ii, jj| ii % jj)
             },
-            (Integer(ii), Decimal(jj)) => {
-                Ok(Decimal(*ii as f64 / *jj))
+            (Decimal(_) | Exact(_), _) | (_, Decimal(_) | Exact(_)) => {
+                Ok(Decimal(self.to_f64() % rhs.to_f64()))
             },
-            (Decimal(ii), Integer(jj)) => {
-                Ok(Decimal(*ii / *jj as f64))
+            _ => Ok(Self::big_op(self, rhs, |ii, jj| ii % jj)),
+        }
+    }
+
+    pub fn abs_ref(&self, mode: OverflowMode) -> Result<Number> {
+        match self {
+            Integer(ii) if *ii < 0 => self.neg_ref(mode),
+            Big(big) if big.sign() == Sign::Minus => self.neg_ref(mode),
+            Decimal(ff) => Ok(Decimal(ff.abs())),
+            Exact(exact) => Ok(Exact(exact.abs())),
+            _ => Ok(self.clone()),
+        }
+    }
+
+    /**
+     * integer powers of integers stay integers, others are computed as f64
+     */
+    pub fn pow_ref(&self, exp: &Self, mode: OverflowMode) -> Result<Number> {
+        let exp_int = match exp {
+            Integer(jj) => u32::try_from(*jj).ok(),
+            _ => None,
+        };
+        match (self, exp_int) {
+            (Integer(ii), Some(jj)) => match ii.checked_pow(jj) {
+                Some(ret) => Ok(Integer(ret)),
+                // the float doesn't need the exact power, which can be huge
+                None if mode == OverflowMode::Decimal => Ok(Decimal((*ii as f64).powf(jj as f64))),
+                None if mode == OverflowMode::BigInt => Ok(Self::from_big(Self::big_pow(&BigInt::from(*ii), jj)?)),
+                None => Self::overflow(mode, format!("pow({}, {})", ii, jj), || BigInt::from(*ii).pow(jj)),
+            },
+            (Big(big), Some(jj)) => Ok(Self::from_big(Self::big_pow(big, jj)?)),
+            (Exact(exact), Some(jj)) => Self::exact_pow(*exact, jj)
+                .map(Exact)
+                .ok_or_else(|| dbg_format!("decimal overflow: pow({}, {})", exact, jj)),
+            _ => Ok(Decimal(self.to_f64().powf(exp.to_f64()))),
+        }
+    }
+
+    // power of a big integer, fails before computing a result over `BIG_POW_BITS_MAX'
+    fn big_pow(base: &BigInt, exp: u32) -> Result<BigInt> {
+        let bits = base.bits().saturating_mul(exp as u64);
+        if bits > BIG_POW_BITS_MAX {
+            return Err(dbg_format!("integer too large: pow({}, {}) has about {} bits, the limit is {}",
+                base, exp, bits, BIG_POW_BITS_MAX));
+        }
+        Ok(base.pow(exp))
+    }
+
+    // exponentiation by squaring, None if it overflows
+    fn exact_pow(mut base: ExactDecimal, mut exp: u32) -> Option<ExactDecimal> {
+        let mut ret = ExactDecimal::ONE;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                ret = ret.checked_mul(base)?;
+            }
+            exp >>= 1;
+            if exp > 0 {
+                base = base.checked_mul(base)?;
             }
         }
+        Some(ret)
+    }
+
+    /**
+     * round to an integer with `op', decimals become integers if they fit in i64
+     */
+    pub fn round_with(&self, op: fn(f64) -> f64, op_exact: fn(&ExactDecimal) -> ExactDecimal) -> Number {
+        match self {
+            Integer(_) | Big(_) => self.clone(),
+            Decimal(ff) => {
+                let ret = op(*ff);
+                match ret.is_finite() && ret.abs() < i64::MAX as f64 {
+                    true => Integer(ret as i64),
+                    false => Decimal(ret),
+                }
+            },
+            Exact(exact) => {
+                let ret = op_exact(exact);
+                match ret.to_i64() {
+                    Some(ii) => Integer(ii),
+                    None => Exact(ret),
+                }
+            },
+        }
+    }
+
+    pub fn neg_ref(&self, mode: OverflowMode) -> Result<Number> {
+        match self {
+            Integer(ii) => match ii.checked_neg() {
+                Some(ii) => Ok(Integer(ii)),
+                None => Self::overflow(mode, format!("-({})", ii), || -BigInt::from(*ii)),
+            },
+            Decimal(ff) => Ok(Decimal(-ff)),
+            Big(big) => Ok(Self::from_big(-big)),
+            Exact(exact) => Ok(Exact(-exact)),
+        }
+    }
+
+    // integer operation, `op_big' gives the exact result when it overflows
+    fn int_op(ii: i64, jj: i64, mode: OverflowMode, sym: &str,
+        op: fn(i64, i64) -> Option<i64>, op_big: fn(BigInt, BigInt) -> BigInt) -> Result<Number> {
+        match op(ii, jj) {
+            Some(ret) => Ok(Integer(ret)),
+            None => Self::overflow(mode, format!("{} {} {}", ii, sym, jj), || op_big(BigInt::from(ii), BigInt::from(jj))),
+        }
+    }
+
+    // operation on integers with at least one big integer
+    fn big_op(lhs: &Self, rhs: &Self, op: fn(BigInt, BigInt) -> BigInt) -> Number {
+        match (lhs.to_big(), rhs.to_big()) {
+            (Some(ii), Some(jj)) => Self::from_big(op(ii, jj)),
+            _ => unreachable!("decimals are handled before"),
+        }
+    }
+
+    // `exact' computes the exact result, only called when the mode needs it
+    fn overflow(mode: OverflowMode, expr: String, exact: impl FnOnce() -> BigInt) -> Result<Number> {
+        match mode {
+            OverflowMode::Error => Err(dbg_format!("integer overflow: {}", expr)),
+            OverflowMode::Decimal => Ok(Decimal(exact().to_f64().unwrap_or(f64::NAN))),
+            OverflowMode::BigInt => Ok(Self::from_big(exact())),
+        }
     }
 }
 
@@ -172,15 +534,14 @@ impl PartialOrd for Number {
             (Integer(ii), Integer(jj)) => {
                 ii.partial_cmp(jj)
             },
-            (Decimal(ii), Decimal(jj)) => {
-                ii.partial_cmp(jj)
+            _ if self.is_exact_pair(other) => match (self.to_exact(), other.to_exact()) {
+                (Some(ii), Some(jj)) => ii.partial_cmp(&jj),
+                _ => self.to_f64().partial_cmp(&other.to_f64()),
             },
-            (Integer(ii), Decimal(jj)) => {
-                (*ii as f64).partial_cmp(jj)
+            (Decimal(_) | Exact(_), _) | (_, Decimal(_) | Exact(_)) => {
+                self.to_f64().partial_cmp(&other.to_f64())
             },
-            (Decimal(ii), Integer(jj)) => {
-                ii.partial_cmp(&(*jj as f64))
-            }
+            _ => self.to_big().partial_cmp(&other.to_big()),
         }
     }
 
@@ -201,21 +562,24 @@ impl PartialOrd for Number {
     }
 }
 
+/**
+ * numbers of different types are compared as f64,
+ * except that integers and exact decimals are compared exactly
+ */
 impl PartialEq for Number {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Integer(ii), Integer(jj)) => {
                 ii.eq(jj)
             },
-            (Decimal(ii), Decimal(jj)) => {
+            (Big(ii), Big(jj)) => {
                 ii.eq(jj)
             },
-            (Integer(ii), Decimal(jj)) => {
-                (*ii as f64) == *jj
+            _ if self.is_exact_pair(other) => match (self.to_exact(), other.to_exact()) {
+                (Some(ii), Some(jj)) => ii.eq(&jj),
+                _ => self.to_f64() == other.to_f64(),
             },
-            (Decimal(ii), Integer(jj)) => {
-                *ii == (*jj as f64)
-            }
+            _ => self.to_f64() == other.to_f64(),
         }
     }
 
@@ -224,3 +588,10 @@ impl PartialEq for Number {
     }
 }
 
+// equal numbers have the same value as f64, so they are hashed as f64
+impl Hash for Number {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // adding 0.0 turns -0.0 into 0.0, they are equal
+        (self.to_f64() + 0.0).to_bits().hash(state);
+    }
+}
@@ -1,40 +1,61 @@
 
-use std::fmt::Display;
+use std::{cell::RefCell, collections::{BTreeMap, HashSet}, fmt::Display, thread::LocalKey};
 
-use crate::{dbg_format, syntax::statement::Stmt};
+use crate::{dbg_format, vm::{gc, native::NativeFn}};
 
-use super::{common::{Crc, Result}, number::Number, shared_str::{SharedStr, SharedStrExt}};
+use super::{common::{Crc, Result}, function::LoxFunctionRc, number::{Number, OverflowMode}, shared_str::{SharedStr, SharedStrExt}};
 
 
+/**
+ * objects are shared through `ObjectRc' and never copied, so a container
+ * changed in place is seen by every holder
+ */
 #[derive(Debug)]
 pub enum Object {
     Nil,
     Boolean(bool),
     Number(Number),
     String(SharedStr),
-    Function(Vec<SharedStr>, Stmt),
+    Function(LoxFunctionRc),
+    Native(NativeFn),
+    // containers can be changed by `push' and `set', so they can form cycles
+    List(RefCell<Vec<ObjectRc>>),
+    Map(RefCell<BTreeMap<SharedStr, ObjectRc>>),
 }
 
 impl Object {
     pub fn new_string(s: String) -> Self {
         Self::String(s.to_share())
     }
-}
 
-pub type ObjectRc = Crc<Object>;
+    pub fn new_list(items: Vec<ObjectRc>) -> Self {
+        Self::List(RefCell::new(items))
+    }
 
-impl Clone for Object {
-    fn clone(&self) -> Self {
+    pub fn new_map(items: BTreeMap<SharedStr, ObjectRc>) -> Self {
+        Self::Map(RefCell::new(items))
+    }
+
+    /**
+     * name of the type, used by error messages
+     */
+    pub fn type_name(&self) -> &'static str {
         match self {
-            Self::Nil => Self::Nil,
-            Self::Boolean(arg0) => Self::Boolean(arg0.clone()),
-            Self::Number(arg0) => Self::Number(arg0.clone()),
-            Self::String(arg0) => Self::String(arg0.clone()),
-            Self::Function(params, body) => Self::Function(params.clone(), body.clone()),
+            Self::Nil => "Nil",
+            Self::Boolean(_) => "Boolean",
+            Self::Number(Number::Integer(_) | Number::Big(_)) => "Integer",
+            Self::Number(Number::Decimal(_)) => "Decimal",
+            Self::Number(Number::Exact(_)) => "Exact",
+            Self::String(_) => "String",
+            Self::Function(_) | Self::Native(_) => "Function",
+            Self::List(_) => "List",
+            Self::Map(_) => "Map",
         }
     }
 }
 
+pub type ObjectRc = Crc<Object>;
+
 impl Display for Object {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -42,13 +63,108 @@ impl Display for Object {
             Self::Boolean(b) => write!(f, "{}", b),
             Self::Number(num) => write!(f, "{}", num),
             Self::String(str) => write!(f, "{}", str),
-            Self::Function(params, body) => write!(f, "({:#?}) {:#?}", params, body),
+            Self::Function(fun) => write!(f, "({:#?}) {:#?}", fun.params, fun.body),
+            Self::Native(native) => write!(f, "{:?}", native),
+            // a container already being printed is part of a cycle
+            Self::List(list) => match CycleGuard::enter(&PRINTING, self) {
+                Some(_guard) => {
+                    let items: Vec<String> = list.borrow().iter().map(|item| item.repr()).collect();
+                    write!(f, "[{}]", items.join(", "))
+                },
+                None => write!(f, "[...]"),
+            },
+            Self::Map(map) => match CycleGuard::enter(&PRINTING, self) {
+                Some(_guard) => {
+                    let items: Vec<String> = map.borrow().iter()
+                        .map(|(key, value)| format!("\"{}\": {}", key, value.repr()))
+                        .collect();
+                    write!(f, "{{{}}}", items.join(", "))
+                },
+                None => write!(f, "{{...}}"),
+            },
         }
     }
 }
 
+thread_local! {
+    // containers being printed by this thread
+    static PRINTING: RefCell<HashSet<*const Object>> = RefCell::new(HashSet::new());
+}
+
+/**
+ * marks a container as visited by a recursive walk, such as printing,
+ * until dropped. Entering a container already visited means a cycle
+ */
+pub struct CycleGuard {
+    visiting: &'static LocalKey<RefCell<HashSet<*const Object>>>,
+    key: *const Object,
+}
+
+impl CycleGuard {
+    /**
+     * ret: None if `obj' is already visited
+     */
+    pub fn enter(visiting: &'static LocalKey<RefCell<HashSet<*const Object>>>, obj: &Object) -> Option<Self> {
+        let key = obj as *const Object;
+        visiting.with(|set| set.borrow_mut().insert(key)).then_some(Self { visiting, key })
+    }
+}
+
+impl Drop for CycleGuard {
+    fn drop(&mut self) {
+        self.visiting.with(|set| set.borrow_mut().remove(&self.key));
+    }
+}
+
+// gc related
 impl Object {
 
+    /**
+     * check if the object can hold references to other objects,
+     * only those can be part of a cycle
+     */
+    pub fn holds_refs(&self) -> bool {
+        matches!(self, Self::List(_) | Self::Map(_))
+    }
+
+    /**
+     * visit every object directly referenced by this object
+     */
+    pub fn trace(&self, visit: &mut dyn FnMut(&ObjectRc)) {
+        match self {
+            Self::List(list) => list.borrow().iter().for_each(visit),
+            Self::Map(map) => map.borrow().values().for_each(visit),
+            _ => {},
+        }
+    }
+
+    /**
+     * drop the references held by this object, used to break cycles
+     */
+    pub fn unlink(&self) {
+        // the items are dropped after the borrow ends
+        match self {
+            Self::List(list) => drop(list.take()),
+            Self::Map(map) => drop(map.take()),
+            _ => {},
+        }
+    }
+
+}
+
+impl Object {
+
+    /**
+     * the format used by the REPL to show values, same as `Display`
+     * but strings are quoted
+     */
+    pub fn repr(&self) -> String {
+        match self {
+            Self::String(str) => format!("\"{}\"", str),
+            _ => self.to_string(),
+        }
+    }
+
     pub fn is_true(&self) -> Result<bool> {
         match self {
             Self::Boolean(b) => Ok(*b),
@@ -66,9 +182,9 @@ impl Object {
         }
     }
 
-    pub fn neg(&self) -> Result<Object> {
+    pub fn neg(&self, mode: OverflowMode) -> Result<Object> {
         match self {
-            Object::Number(num) => Ok(Object::Number(-num.clone())),
+            Object::Number(num) => Ok(Object::Number(num.neg_ref(mode)?)),
             _ => Err(dbg_format!("not supported operation `Not(!)' on {:#?}", self))
         }
     }
@@ -81,11 +197,11 @@ impl Object {
         Ok(Object::Boolean(self.is_true()? || rhs.is_true()?))
     }
 
-    pub fn add(&self, rhs: &Self) -> Result<Object> {
+    pub fn add(&self, rhs: &Self, mode: OverflowMode) -> Result<Object> {
         use Object::*;
         match (self, rhs) {
             (Number(arg1), Number(arg2)) => {
-                Ok(Object::Number(arg1.add_ref(&arg2)?))
+                Ok(Object::Number(arg1.add_ref(arg2, mode)?))
             },
             (String(arg1), String(arg2)) => {
                 Ok(Object::new_string(format!("{}{}", arg1, arg2)))
@@ -100,51 +216,89 @@ impl Object {
         }
     }
 
-    pub fn sub(&self, rhs: &Self) -> Result<Object> {
+    pub fn sub(&self, rhs: &Self, mode: OverflowMode) -> Result<Object> {
         use Object::*;
         match (self, rhs) {
             (Number(arg1), Number(arg2)) => {
-                Ok(Object::Number(arg1.sub_ref(arg2)?))
+                Ok(Object::Number(arg1.sub_ref(arg2, mode)?))
             },
             _ => Err(dbg_format!("object type not allowed {:#?} == {:#?}", self, rhs)),
         }
     }
 
-    pub fn mul(&self, rhs: &Self) -> Result<Object> {
+    pub fn mul(&self, rhs: &Self, mode: OverflowMode) -> Result<Object> {
         use Object::*;
         match (self, rhs) {
             (Number(arg1), Number(arg2)) => {
-                Ok(Object::Number(arg1.mul_ref(arg2)?))
+                Ok(Object::Number(arg1.mul_ref(arg2, mode)?))
             },
             _ => Err(dbg_format!("object type not allowed {:#?} == {:#?}", self, rhs)),
         }
     }
 
-    pub fn div(&self, rhs: &Self) -> Result<Object> {
+    pub fn div(&self, rhs: &Self, mode: OverflowMode) -> Result<Object> {
         use Object::*;
         match (self, rhs) {
             (Number(arg1), Number(arg2)) => {
-                Ok(Object::Number(arg1.div_ref(&arg2)?))
+                Ok(Object::Number(arg1.div_ref(arg2, mode)?))
             },
             _ => Err(dbg_format!("object type not allowed {:#?} == {:#?}", self, rhs)),
         }
     }
 
-    pub fn eq(&self, rhs: &Self) -> Result<Object> {
+    pub fn rem(&self, rhs: &Self, mode: OverflowMode) -> Result<Object> {
         use Object::*;
         match (self, rhs) {
-            (Nil, Nil) => Ok(Object::Boolean(true)),
-            (Boolean(arg1), Boolean(arg2)) => {
-                Ok(Object::Boolean(arg1 == &arg2.clone()))
-            },
             (Number(arg1), Number(arg2)) => {
-                Ok(Object::Boolean(arg1 == &arg2.clone()))
+                Ok(Object::Number(arg1.rem_ref(arg2, mode)?))
             },
-            (String(arg1), String(arg2)) => {
-                Ok(Object::Boolean(arg1 == &arg2.clone()))
+            _ => Err(dbg_format!("object type not allowed {:#?} == {:#?}", self, rhs)),
+        }
+    }
+
+    pub fn eq(&self, rhs: &Self) -> Result<Object> {
+        Ok(Object::Boolean(self.eq_seen(rhs, &mut HashSet::new())?))
+    }
+
+    // seen: pairs of containers being compared, a pair met again is
+    // part of a cycle and taken as equal
+    fn eq_seen(&self, rhs: &Self, seen: &mut HashSet<(*const Object, *const Object)>) -> Result<bool> {
+        use Object::*;
+        match (self, rhs) {
+            (Nil, Nil) => Ok(true),
+            (Boolean(arg1), Boolean(arg2)) => Ok(arg1 == arg2),
+            (Number(arg1), Number(arg2)) => Ok(arg1 == arg2),
+            (String(arg1), String(arg2)) => Ok(arg1 == arg2),
+            // functions are equal only if they come from the same declaration
+            (Function(arg1), Function(arg2)) => Ok(Crc::ptr_eq(arg1, arg2)),
+            (List(_), List(_)) | (Map(_), Map(_)) if !seen.insert((self as *const _, rhs as *const _)) => Ok(true),
+            (List(arg1), List(arg2)) => {
+                let (arg1, arg2) = (arg1.borrow(), arg2.borrow());
+                let mut equal = arg1.len() == arg2.len();
+                for (item1, item2) in arg1.iter().zip(arg2.iter()) {
+                    if !equal {
+                        break;
+                    }
+                    equal = item1.eq_seen(item2, seen)?;
+                }
+                Ok(equal)
+            },
+            (Map(arg1), Map(arg2)) => {
+                let (arg1, arg2) = (arg1.borrow(), arg2.borrow());
+                let mut equal = arg1.len() == arg2.len();
+                for (key, item1) in arg1.iter() {
+                    if !equal {
+                        break;
+                    }
+                    equal = match arg2.get(key) {
+                        Some(item2) => item1.eq_seen(item2, seen)?,
+                        None => false,
+                    };
+                }
+                Ok(equal)
             },
             // false if type mismatch
-            _ => Ok(Object::Boolean(false)),
+            _ => Ok(false),
         }
     }
 
@@ -201,15 +355,19 @@ impl Object {
 impl Object {
 
     pub fn to_rc(self) -> ObjectRc {
-        Crc::new(self)
+        let obj = Crc::new(self);
+        if obj.holds_refs() {
+            gc::created(&obj);
+        }
+        obj
     }
 
     pub fn not_rc(&self) -> Result<ObjectRc> {
         Ok(Object::not(self)?.to_rc())
     }
 
-    pub fn neg_rc(&self) -> Result<ObjectRc> {
-        Ok(Object::neg(self)?.to_rc())
+    pub fn neg_rc(&self, mode: OverflowMode) -> Result<ObjectRc> {
+        Ok(Object::neg(self, mode)?.to_rc())
     }
 
     pub fn logic_and_rc(&self, rhs: ObjectRc) -> Result<ObjectRc> {
@@ -220,20 +378,24 @@ impl Object {
         Ok(Object::logic_or(self, &*rhs)?.to_rc())
     }
 
-    pub fn add_rc(&self, rhs: ObjectRc) -> Result<ObjectRc> {
-        Ok(Object::add(self, &*rhs)?.to_rc())
+    pub fn add_rc(&self, rhs: ObjectRc, mode: OverflowMode) -> Result<ObjectRc> {
+        Ok(Object::add(self, &rhs, mode)?.to_rc())
+    }
+
+    pub fn sub_rc(&self, rhs: ObjectRc, mode: OverflowMode) -> Result<ObjectRc> {
+        Ok(Object::sub(self, &rhs, mode)?.to_rc())
     }
 
-    pub fn sub_rc(&self, rhs: ObjectRc) -> Result<ObjectRc> {
-        Ok(Object::sub(self, &*rhs)?.to_rc())
+    pub fn mul_rc(&self, rhs: ObjectRc, mode: OverflowMode) -> Result<ObjectRc> {
+        Ok(Object::mul(self, &rhs, mode)?.to_rc())
     }
 
-    pub fn mul_rc(&self, rhs: ObjectRc) -> Result<ObjectRc> {
-        Ok(Object::mul(self, &*rhs)?.to_rc())
+    pub fn div_rc(&self, rhs: ObjectRc, mode: OverflowMode) -> Result<ObjectRc> {
+        Ok(Object::div(self, &rhs, mode)?.to_rc())
     }
 
-    pub fn div_rc(&self, rhs: ObjectRc) -> Result<ObjectRc> {
-        Ok(Object::div(self, &*rhs)?.to_rc())
+    pub fn rem_rc(&self, rhs: ObjectRc, mode: OverflowMode) -> Result<ObjectRc> {
+        Ok(Object::rem(self, &rhs, mode)?.to_rc())
     }
 
     pub fn eq_rc(&self, rhs: ObjectRc) -> Result<ObjectRc> {
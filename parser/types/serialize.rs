@@ -0,0 +1,160 @@
+use std::{cell::RefCell, collections::{BTreeMap, HashSet}, fmt};
+
+use serde::{
+    de::{MapAccess, SeqAccess, Visitor},
+    ser::{Error, SerializeMap, SerializeSeq},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+
+use super::{
+    number::Number,
+    object::{CycleGuard, Object, ObjectRc},
+    shared_str::{SharedStr, SharedStrExt},
+    value::Value,
+};
+
+
+thread_local! {
+    // containers being serialized by this thread
+    static SERIALIZING: RefCell<HashSet<*const Object>> = RefCell::new(HashSet::new());
+}
+
+/**
+ * only data-like objects can be serialized, exact decimals are strings,
+ * functions, non-finite numbers and cycles are errors
+ */
+impl Serialize for Object {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // a container already being serialized is part of a cycle
+        let _guard = match self {
+            Self::List(_) | Self::Map(_) => match CycleGuard::enter(&SERIALIZING, self) {
+                Some(guard) => Some(guard),
+                None => return Err(S::Error::custom(format!("cannot serialize a cyclic {}", self.type_name()))),
+            },
+            _ => None,
+        };
+        match self {
+            Self::Nil => serializer.serialize_unit(),
+            Self::Boolean(b) => serializer.serialize_bool(*b),
+            Self::Number(Number::Integer(ii)) => serializer.serialize_i64(*ii),
+            Self::Number(Number::Decimal(ff)) if ff.is_finite() => serializer.serialize_f64(*ff),
+            Self::Number(Number::Decimal(ff)) => Err(S::Error::custom(format!("cannot serialize number {}", ff))),
+            // as a string, a json number is read as f64 and would lose digits
+            Self::Number(Number::Exact(exact)) => serializer.collect_str(exact),
+            Self::Number(Number::Big(big)) => match big.to_i128() {
+                Some(ii) => serializer.serialize_i128(ii),
+                None => Err(S::Error::custom(format!("cannot serialize integer {} beyond 128 bits", big))),
+            },
+            Self::String(str) => serializer.serialize_str(str),
+            Self::List(list) => {
+                let list = list.borrow();
+                let mut seq = serializer.serialize_seq(Some(list.len()))?;
+                for item in list.iter() {
+                    seq.serialize_element(item.as_ref())?;
+                }
+                seq.end()
+            },
+            Self::Map(map) => {
+                let map = map.borrow();
+                let mut ser = serializer.serialize_map(Some(map.len()))?;
+                for (key, item) in map.iter() {
+                    ser.serialize_entry(key.as_ref(), item.as_ref())?;
+                }
+                ser.end()
+            },
+            Self::Function(_) | Self::Native(_) => Err(S::Error::custom(format!("cannot serialize {}", self.type_name()))),
+        }
+    }
+}
+
+struct ObjectVisitor;
+
+impl<'de> Visitor<'de> for ObjectVisitor {
+    type Value = Object;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "nil, boolean, number, string, list or map")
+    }
+
+    fn visit_unit<E>(self) -> Result<Object, E> {
+        Ok(Object::Nil)
+    }
+
+    fn visit_none<E>(self) -> Result<Object, E> {
+        Ok(Object::Nil)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Object, D::Error> {
+        Object::deserialize(deserializer)
+    }
+
+    fn visit_bool<E>(self, b: bool) -> Result<Object, E> {
+        Ok(Object::Boolean(b))
+    }
+
+    fn visit_i64<E>(self, ii: i64) -> Result<Object, E> {
+        Ok(Object::Number(Number::Integer(ii)))
+    }
+
+    // integers beyond i64 become big integers
+    fn visit_u64<E>(self, uu: u64) -> Result<Object, E> {
+        Ok(Object::Number(Number::from_big(BigInt::from(uu))))
+    }
+
+    fn visit_i128<E>(self, ii: i128) -> Result<Object, E> {
+        Ok(Object::Number(Number::from_big(BigInt::from(ii))))
+    }
+
+    fn visit_u128<E>(self, uu: u128) -> Result<Object, E> {
+        Ok(Object::Number(Number::from_big(BigInt::from(uu))))
+    }
+
+    fn visit_f64<E>(self, ff: f64) -> Result<Object, E> {
+        Ok(Object::Number(Number::Decimal(ff)))
+    }
+
+    fn visit_str<E>(self, str: &str) -> Result<Object, E> {
+        Ok(Object::new_string(str.to_string()))
+    }
+
+    fn visit_string<E>(self, str: String) -> Result<Object, E> {
+        Ok(Object::new_string(str))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Object, A::Error> {
+        let mut list: Vec<ObjectRc> = Vec::new();
+        while let Some(item) = seq.next_element::<Object>()? {
+            list.push(item.to_rc());
+        }
+        Ok(Object::new_list(list))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Object, A::Error> {
+        let mut map: BTreeMap<SharedStr, ObjectRc> = BTreeMap::new();
+        while let Some((key, item)) = access.next_entry::<String, Object>()? {
+            map.insert(key.to_share(), item.to_rc());
+        }
+        Ok(Object::new_map(map))
+    }
+}
+
+impl<'de> Deserialize<'de> for Object {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ObjectVisitor)
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_rc().as_ref().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Value::from_rc(Object::deserialize(deserializer)?.to_rc()))
+    }
+}
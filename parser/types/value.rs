@@ -0,0 +1,130 @@
+use std::fmt::Display;
+
+use super::{
+    common::Result,
+    convert::FromLox,
+    number::Number,
+    object::{Object, ObjectRc},
+    shared_str::SharedStrExt,
+};
+
+
+/**
+ * a lox value handed to or returned from the host
+ */
+#[derive(Debug, Clone)]
+pub struct Value(ObjectRc);
+
+impl Value {
+    pub fn nil() -> Self {
+        Self(Object::Nil.to_rc())
+    }
+
+    pub fn from_rc(obj: ObjectRc) -> Self {
+        Self(obj)
+    }
+
+    pub fn to_rc(&self) -> ObjectRc {
+        self.0.clone()
+    }
+
+    pub fn is_nil(&self) -> bool {
+        matches!(self.0.as_ref(), Object::Nil)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.0.as_ref() {
+            Object::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.0.as_ref() {
+            Object::Number(Number::Integer(ii)) => Some(*ii),
+            _ => None,
+        }
+    }
+
+    /**
+     * get the number as f64, integers are converted
+     */
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.0.as_ref() {
+            Object::Number(num) => Some(num.to_f64()),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self.0.as_ref() {
+            Object::String(str) => Some(str),
+            _ => None,
+        }
+    }
+
+    /**
+     * convert to a rust value, fails if the type mismatch
+     */
+    pub fn get<T: FromLox>(&self) -> Result<T> {
+        T::from_lox(&self.0)
+    }
+
+    pub fn is_callable(&self) -> bool {
+        matches!(self.0.as_ref(), Object::Function(_) | Object::Native(_))
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(self.0.eq(&other.0), Ok(Object::Boolean(true)))
+    }
+}
+
+impl From<()> for Value {
+    fn from(_: ()) -> Self {
+        Self::nil()
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Self(Object::Boolean(b).to_rc())
+    }
+}
+
+impl From<i32> for Value {
+    fn from(ii: i32) -> Self {
+        Self(Object::Number(Number::Integer(ii as i64)).to_rc())
+    }
+}
+
+impl From<i64> for Value {
+    fn from(ii: i64) -> Self {
+        Self(Object::Number(Number::Integer(ii)).to_rc())
+    }
+}
+
+impl From<f64> for Value {
+    fn from(ff: f64) -> Self {
+        Self(Object::Number(Number::Decimal(ff)).to_rc())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(str: &str) -> Self {
+        Self(Object::String(str.to_string().to_share()).to_rc())
+    }
+}
+
+impl From<String> for Value {
+    fn from(str: String) -> Self {
+        Self(Object::String(str.to_share()).to_rc())
+    }
+}
@@ -1,66 +1,207 @@
+use std::{fmt::Debug, io::Write};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputChannel {
+    Stdout,
+    Stderr,
+}
+
+/**
+ * Line: output is passed to the sink when a line is completed
+ * Unbuffered: output is passed to the sink on every print
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferMode {
+    Line,
+    Unbuffered,
+}
+
+/**
+ * where the output of a channel goes
+ *
+ * Buffer: kept in memory until `take()'
+ * Console: the stdout/stderr of the process
+ * Writer: any `std::io::Write'
+ * Callback: called with every piece of output
+ */
+pub enum PrinterSink {
+    Buffer,
+    Console,
+    Writer(Box<dyn Write>),
+    Callback(Box<dyn FnMut(&str)>),
+}
+
+impl Debug for PrinterSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Buffer => write!(f, "Buffer"),
+            Self::Console => write!(f, "Console"),
+            Self::Writer(_) => write!(f, "Writer"),
+            Self::Callback(_) => write!(f, "Callback"),
+        }
+    }
+}
 
 #[derive(Debug)]
-pub struct LoxPrinter {
+struct PrinterChannel {
+    channel: OutputChannel,
+    sink: PrinterSink,
     buffer: String,
-    to_console: bool,
+}
+
+impl PrinterChannel {
+    fn new(channel: OutputChannel, sink: PrinterSink) -> Self {
+        Self {
+            channel,
+            sink,
+            buffer: String::new(),
+        }
+    }
+
+    fn write(&mut self, msg: &str, mode: BufferMode) {
+        self.buffer.push_str(msg);
+        let end = match mode {
+            BufferMode::Unbuffered => self.buffer.len(),
+            BufferMode::Line => self.buffer.rfind('\n').map_or(0, |idx| idx + 1),
+        };
+        self.emit(end);
+    }
+
+    fn flush(&mut self) {
+        self.emit(self.buffer.len());
+    }
+
+    // pass the first `end` bytes of the buffer to the sink
+    fn emit(&mut self, end: usize) {
+        if end == 0 {
+            return;
+        }
+        match &mut self.sink {
+            PrinterSink::Buffer => return,
+            PrinterSink::Console => match self.channel {
+                OutputChannel::Stdout => {
+                    print!("{}", &self.buffer[..end]);
+                    let _ = std::io::stdout().flush();
+                },
+                OutputChannel::Stderr => eprint!("{}", &self.buffer[..end]),
+            },
+            PrinterSink::Writer(writer) => {
+                // output errors must not stop the script
+                let _ = writer.write_all(&self.buffer.as_bytes()[..end]);
+                let _ = writer.flush();
+            },
+            PrinterSink::Callback(func) => func(&self.buffer[..end]),
+        }
+        self.buffer.drain(..end);
+    }
+}
+
+#[derive(Debug)]
+pub struct LoxPrinter {
+    out: PrinterChannel,
+    err: PrinterChannel,
+    mode: BufferMode,
 }
 
 impl LoxPrinter {
 
     pub fn new() -> Self {
         LoxPrinter {
-            buffer: String::new(),
-            to_console: true,
+            out: PrinterChannel::new(OutputChannel::Stdout, PrinterSink::Console),
+            err: PrinterChannel::new(OutputChannel::Stderr, PrinterSink::Console),
+            mode: BufferMode::Unbuffered,
         }
     }
 
     pub fn clear(&mut self) {
-        self.buffer.clear();
+        self.out.buffer.clear();
+        self.err.buffer.clear();
     }
 
 }
 
 impl LoxPrinter {
 
+    fn channel_mut(&mut self, channel: OutputChannel) -> &mut PrinterChannel {
+        match channel {
+            OutputChannel::Stdout => &mut self.out,
+            OutputChannel::Stderr => &mut self.err,
+        }
+    }
+
+    /**
+     * keep output in buffer, or print to the console directly
+     * for both channels
+     */
     #[allow(dead_code)]
     pub fn auto_to_console(&mut self, to_console: bool) {
-        self.to_console = to_console;
+        for channel in [OutputChannel::Stdout, OutputChannel::Stderr] {
+            let sink = match to_console {
+                true => PrinterSink::Console,
+                false => PrinterSink::Buffer,
+            };
+            self.sink_set(channel, sink);
+        }
     }
 
-    fn try_flush(&mut self) {
-        if self.to_console {
-            print!("{}", self.buffer);
-            self.buffer.clear();
-        }
+    /**
+     * set where the output of a channel goes,
+     * output still buffered for the old sink is passed to it first
+     */
+    pub fn sink_set(&mut self, channel: OutputChannel, sink: PrinterSink) {
+        let channel = self.channel_mut(channel);
+        channel.flush();
+        channel.sink = sink;
     }
 
-    #[allow(dead_code)]
-    pub fn flush_to_console(&mut self) {
-        if self.buffer.is_empty() {
-            return;
-        }
-        print!("{}", self.buffer);
-        self.buffer.clear();
+    pub fn mode_set(&mut self, mode: BufferMode) {
+        self.mode = mode;
+    }
+
+    /**
+     * pass all buffered output to the sinks,
+     * the output is kept if the sink is `Buffer'
+     */
+    pub fn flush(&mut self) {
+        self.out.flush();
+        self.err.flush();
     }
 
     #[allow(dead_code)]
     pub fn print(&mut self, msg: &str) {
-        self.buffer.push_str(msg);
-        self.try_flush();
+        self.out.write(msg, self.mode);
     }
 
     #[allow(dead_code)]
     pub fn println(&mut self, msg: &str) {
-        self.buffer.push_str(msg);
-        self.buffer.push_str("\n");
-        self.try_flush();
+        self.print(&format!("{}\n", msg));
     }
 
+    pub fn eprint(&mut self, msg: &str) {
+        self.err.write(msg, self.mode);
+    }
+
+    pub fn eprintln(&mut self, msg: &str) {
+        self.eprint(&format!("{}\n", msg));
+    }
+
+    /**
+     * take the output not passed to the sink yet
+     */
     #[allow(dead_code)]
     pub fn take(&mut self) -> String {
-        let ret = self.buffer.clone();
-        self.buffer.clear();
-        ret
+        std::mem::take(&mut self.out.buffer)
+    }
+
+    pub fn take_err(&mut self) -> String {
+        std::mem::take(&mut self.err.buffer)
     }
 
 }
+
+impl Drop for LoxPrinter {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
@@ -0,0 +1,62 @@
+use crate::{
+    dbg_format,
+    types::{
+        common::{Crc, Result},
+        object::{Object, ObjectRc},
+    },
+    vm::{string::{index_of, position}, vm::LoxVM},
+};
+
+
+// builtins which are methods of lists and maps, called as `obj.name(args)'
+pub const LIST_METHODS: [&str; 5] = ["len", "join", "contains", "push", "set"];
+pub const MAP_METHODS: [&str; 2] = ["len", "set"];
+
+/**
+ * obj[idx] = value: replace an item of a list, or set the value of a key in a map
+ */
+pub fn index_set(obj: &ObjectRc, idx: &ObjectRc, value: ObjectRc) -> Result<()> {
+    match obj.as_ref() {
+        Object::List(list) => {
+            let pos = index_of(idx)?;
+            let mut list = list.borrow_mut();
+            let len = list.len();
+            match position(pos, len) {
+                Some(pos) => list[pos] = value,
+                None => return Err(dbg_format!("list index {} out of range", pos)),
+            }
+        },
+        Object::Map(map) => match idx.as_ref() {
+            Object::String(key) => {
+                map.borrow_mut().insert(key.clone(), value);
+            },
+            key => return Err(dbg_format!("map key must be a string, got {}", key.type_name())),
+        },
+        obj => return Err(dbg_format!("cannot set items of {}", obj.type_name())),
+    }
+    Ok(())
+}
+
+// natives related
+impl LoxVM {
+
+    /**
+     * natives changing lists and maps in place, the only way to make a cycle
+     */
+    pub fn container_natives_register(&mut self) {
+        // push(list, item): append the item to the end of the list
+        self.native_add("push", Some(2), Crc::new(|_vm, args| {
+            match args[0].as_ref() {
+                Object::List(list) => list.borrow_mut().push(args[1].clone()),
+                obj => return Err(dbg_format!("push: expect List, got {}", obj.type_name())),
+            }
+            Ok(Object::Nil.to_rc())
+        }));
+        // set(obj, idx, value): replace a list item, or set a map key
+        self.native_add("set", Some(3), Crc::new(|_vm, args| {
+            index_set(&args[0], &args[1], args[2].clone())?;
+            Ok(Object::Nil.to_rc())
+        }));
+    }
+
+}
@@ -0,0 +1,232 @@
+use std::{iter::Peekable, str::Chars};
+
+use rust_decimal::RoundingStrategy;
+
+use crate::{
+    dbg_format,
+    types::{
+        common::{Crc, Result},
+        number::Number,
+        object::{Object, ObjectRc},
+    },
+    vm::vm::LoxVM,
+};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+/**
+ * a replacement field such as `{:>8.2}', in the same form as rust:
+ *
+ *   {[index][:[[fill]align][+][0][width][.precision][type]]}
+ *
+ * align: `<' left, `>' right, `^' center
+ * type: `x' `X' `b' `o' for integers in other bases, `e' for exponent
+ */
+#[derive(Debug, Default)]
+struct FormatSpec {
+    index: Option<usize>,
+    fill: Option<char>,
+    align: Option<Align>,
+    sign: bool,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+    kind: Option<char>,
+}
+
+fn align_of(ch: char) -> Option<Align> {
+    match ch {
+        '<' => Some(Align::Left),
+        '>' => Some(Align::Right),
+        '^' => Some(Align::Center),
+        _ => None,
+    }
+}
+
+fn read_digits(chars: &mut Peekable<Chars>) -> Option<usize> {
+    let mut digits = String::new();
+    while let Some(ch) = chars.next_if(char::is_ascii_digit) {
+        digits.push(ch);
+    }
+    digits.parse().ok()
+}
+
+impl FormatSpec {
+    fn parse(field: &str) -> Result<Self> {
+        let mut spec = Self::default();
+        let (index, fmt) = field.split_once(':').unwrap_or((field, ""));
+        if !index.is_empty() {
+            spec.index = Some(index.parse().map_err(|_| dbg_format!("invalid argument index `{}` in format", index))?);
+        }
+
+        let mut chars = fmt.chars().peekable();
+        let mut ahead = fmt.chars();
+        match (ahead.next(), ahead.next().and_then(align_of)) {
+            (Some(fill), Some(align)) => {
+                spec.fill = Some(fill);
+                spec.align = Some(align);
+                chars.nth(1);
+            },
+            (Some(ch), None) if align_of(ch).is_some() => {
+                spec.align = align_of(ch);
+                chars.next();
+            },
+            _ => {},
+        }
+        spec.sign = chars.next_if_eq(&'+').is_some();
+        spec.zero = chars.next_if_eq(&'0').is_some();
+        spec.width = read_digits(&mut chars).unwrap_or(0);
+        if chars.next_if_eq(&'.').is_some() {
+            spec.precision = Some(read_digits(&mut chars).ok_or_else(|| dbg_format!("missing precision in format `{{{}}}`", field))?);
+        }
+        spec.kind = chars.next_if(|ch| matches!(ch, 'x' | 'X' | 'b' | 'o' | 'e'));
+        if let Some(ch) = chars.next() {
+            return Err(dbg_format!("unknown format `{}` in `{{{}}}`", ch, field));
+        }
+        Ok(spec)
+    }
+
+    fn render_number(&self, num: &Number) -> Result<String> {
+        let text = match (self.kind, num) {
+            (Some('x'), Number::Integer(ii)) => Self::radix(*ii < 0, format!("{:x}", ii.unsigned_abs())),
+            (Some('X'), Number::Integer(ii)) => Self::radix(*ii < 0, format!("{:X}", ii.unsigned_abs())),
+            (Some('b'), Number::Integer(ii)) => Self::radix(*ii < 0, format!("{:b}", ii.unsigned_abs())),
+            (Some('o'), Number::Integer(ii)) => Self::radix(*ii < 0, format!("{:o}", ii.unsigned_abs())),
+            (Some('x'), Number::Big(big)) => format!("{:x}", big),
+            (Some('X'), Number::Big(big)) => format!("{:X}", big),
+            (Some('b'), Number::Big(big)) => format!("{:b}", big),
+            (Some('o'), Number::Big(big)) => format!("{:o}", big),
+            (Some('e'), num) => match self.precision {
+                Some(prec) => format!("{:.*e}", prec, num.to_f64()),
+                None => format!("{:e}", num.to_f64()),
+            },
+            (Some(kind), num) => return Err(dbg_format!("format `{}` needs an integer, got {}", kind, num)),
+            (None, num) => match self.precision {
+                None => num.to_string(),
+                Some(prec) => match num {
+                    Number::Decimal(ff) if !ff.is_finite() => num.to_string(),
+                    Number::Decimal(ff) => format!("{:.*}", prec, ff),
+                    // integers and exact decimals are rounded exactly
+                    _ => match num.to_exact() {
+                        Some(exact) => {
+                            let exact = exact.round_dp_with_strategy(prec as u32, RoundingStrategy::MidpointAwayFromZero);
+                            format!("{:.*}", prec, exact)
+                        },
+                        None => format!("{}.{}", num, "0".repeat(prec)),
+                    },
+                },
+            },
+        };
+        match self.sign && !text.starts_with('-') {
+            true => Ok(format!("+{}", text)),
+            false => Ok(text),
+        }
+    }
+
+    fn radix(negative: bool, digits: String) -> String {
+        match negative {
+            true => format!("-{}", digits),
+            false => digits,
+        }
+    }
+
+    fn render(&self, obj: &Object) -> Result<String> {
+        let (text, is_number) = match obj {
+            Object::Number(num) => (self.render_number(num)?, true),
+            Object::String(str) => match self.precision {
+                Some(prec) => (str.chars().take(prec).collect(), false),
+                None => (str.to_string(), false),
+            },
+            _ if self.kind.is_some() => return Err(dbg_format!("format `{}` needs a number, got {}", self.kind.unwrap_or_default(), obj.type_name())),
+            obj => (obj.to_string(), false),
+        };
+
+        let len = text.chars().count();
+        if len >= self.width {
+            return Ok(text);
+        }
+        let pad = self.width - len;
+        // zeros go between the sign and the digits
+        if self.zero && is_number && self.align.is_none() {
+            let (sign, digits) = match text.starts_with(['-', '+']) {
+                true => text.split_at(1),
+                false => ("", text.as_str()),
+            };
+            return Ok(format!("{}{}{}", sign, "0".repeat(pad), digits));
+        }
+
+        let fill = self.fill.unwrap_or(' ').to_string();
+        let align = self.align.unwrap_or(if is_number { Align::Right } else { Align::Left });
+        Ok(match align {
+            Align::Left => format!("{}{}", text, fill.repeat(pad)),
+            Align::Right => format!("{}{}", fill.repeat(pad), text),
+            Align::Center => format!("{}{}{}", fill.repeat(pad / 2), text, fill.repeat(pad - pad / 2)),
+        })
+    }
+}
+
+/**
+ * format the arguments with the replacement fields in `fmt',
+ * `{{' and `}}' are literal braces
+ */
+pub fn format_args(fmt: &str, args: &[ObjectRc]) -> Result<String> {
+    let mut out = String::new();
+    let mut chars = fmt.chars().peekable();
+    let mut next_arg = 0;
+    let mut used = vec![false; args.len()];
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' if chars.next_if_eq(&'{').is_some() => out.push('{'),
+            '}' if chars.next_if_eq(&'}').is_some() => out.push('}'),
+            '{' => {
+                let mut field = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(ch) => field.push(ch),
+                        None => return Err(dbg_format!("unclosed `{` in format")),
+                    }
+                }
+                let spec = FormatSpec::parse(&field)?;
+                let idx = spec.index.unwrap_or_else(|| {
+                    next_arg += 1;
+                    next_arg - 1
+                });
+                let arg = args.get(idx)
+                    .ok_or_else(|| dbg_format!("format needs argument {}, got {} arguments", idx + 1, args.len()))?;
+                used[idx] = true;
+                out.push_str(&spec.render(arg)?);
+            },
+            '}' => return Err(dbg_format!("unmatched `}` in format, use `}}`")),
+            ch => out.push(ch),
+        }
+    }
+    if let Some(idx) = used.iter().position(|used| !used) {
+        return Err(dbg_format!("format argument {} is never used", idx + 1));
+    }
+    Ok(out)
+}
+
+// natives related
+impl LoxVM {
+
+    pub fn format_natives_register(&mut self) {
+        // format(fmt, ...)
+        self.native_add("format", None, Crc::new(|_vm, args| {
+            let fmt = match args.first().map(|arg| arg.as_ref()) {
+                Some(Object::String(fmt)) => fmt.clone(),
+                Some(obj) => return Err(dbg_format!("argument 1 of `format`: type mismatch: expect String, got {}", obj.type_name())),
+                None => return Err(dbg_format!("function `format` expect at least 1 arguments, got 0")),
+            };
+            Ok(Object::new_string(format_args(&fmt, &args[1..])?).to_rc())
+        }));
+    }
+
+}
@@ -0,0 +1,240 @@
+use std::{cell::RefCell, collections::{HashMap, HashSet}};
+
+use crate::types::{
+    common::{Crc, Weak},
+    number::Number,
+    object::{Object, ObjectRc},
+};
+
+use super::vm::LoxVM;
+
+
+/**
+ * threshold: number of tracked objects before the first automatic collection
+ * grow_factor: the next collection happens when tracked objects reach
+ *              `live * grow_factor`, but never below `threshold`
+ * auto_collect: collect automatically when the limit is reached
+ */
+#[derive(Debug, Clone)]
+pub struct GcConfig {
+    pub threshold: usize,
+    pub grow_factor: usize,
+    pub auto_collect: bool,
+}
+
+impl Default for GcConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 1024,
+            grow_factor: 2,
+            auto_collect: true,
+        }
+    }
+}
+
+thread_local! {
+    // objects able to hold references, created since a collector last took them
+    static CREATED: RefCell<Vec<Weak<Object>>> = const { RefCell::new(Vec::new()) };
+}
+
+/**
+ * register a new object which can hold references to other objects, the
+ * next vm polling its collector on this thread starts tracking it
+ */
+pub fn created(obj: &ObjectRc) {
+    CREATED.with(|created| created.borrow_mut().push(Crc::downgrade(obj)));
+}
+
+/**
+ * allocated: objects ever tracked by the collector
+ * freed: tracked objects found released, including broken cycles
+ * cycles: objects released by breaking reference cycles
+ * collections: number of collections run
+ * live: tracked objects still alive after the last collection
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GcStats {
+    pub allocated: usize,
+    pub freed: usize,
+    pub cycles: usize,
+    pub collections: usize,
+    pub live: usize,
+}
+
+/**
+ * the cycle collector for objects
+ *
+ * `Rc` already releases acyclic objects, so the collector only needs to find
+ * objects which are alive but cannot be reached from the vm roots. Those are
+ * checked with trial deletion: an object is only garbage if all of its strong
+ * references come from other unreachable objects, so values held by the host
+ * or by the rust stack during evaluation will never be touched.
+ *
+ * only objects which can hold references can be part of a cycle, they are
+ * registered when created and taken by the collector when the vm polls it
+ */
+#[derive(Debug)]
+pub struct LoxGc {
+    objects: HashMap<*const Object, Weak<Object>>,
+    config: GcConfig,
+    stats: GcStats,
+    next_collect: usize,
+}
+
+impl LoxGc {
+    pub fn new() -> Self {
+        Self::with_config(GcConfig::default())
+    }
+
+    pub fn with_config(config: GcConfig) -> Self {
+        Self {
+            objects: HashMap::new(),
+            next_collect: config.threshold,
+            config,
+            stats: GcStats::default(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.objects.clear();
+        self.stats = GcStats::default();
+        self.next_collect = self.config.threshold;
+    }
+
+    pub fn config(&self) -> &GcConfig {
+        &self.config
+    }
+
+    pub fn config_set(&mut self, config: GcConfig) {
+        self.next_collect = config.threshold.max(self.objects.len());
+        self.config = config;
+    }
+
+    pub fn stats(&self) -> GcStats {
+        self.stats
+    }
+
+    pub fn should_collect(&self) -> bool {
+        self.config.auto_collect && self.objects.len() >= self.next_collect
+    }
+
+    /**
+     * start tracking the objects created since the last call
+     */
+    pub fn adopt(&mut self) {
+        let created = CREATED.with(|created| std::mem::take(&mut *created.borrow_mut()));
+        for weak in created {
+            self.stats.allocated += 1;
+            if weak.strong_count() == 0 {
+                self.stats.freed += 1;
+                continue;
+            }
+            // address reused after the old object was released
+            if self.objects.insert(weak.as_ptr(), weak).is_some() {
+                self.stats.freed += 1;
+            }
+        }
+    }
+
+    /**
+     * run a full collection
+     *
+     * roots: every object directly reachable by the vm
+     *
+     * ret: number of objects freed by this collection
+     */
+    pub fn collect<'a>(&mut self, roots: impl Iterator<Item = &'a ObjectRc>) -> usize {
+        let freed_before = self.stats.freed;
+        self.adopt();
+
+        // drop entries already released by `Rc`
+        let before = self.objects.len();
+        self.objects.retain(|_, weak| weak.strong_count() > 0);
+        self.stats.freed += before - self.objects.len();
+
+        let mut marked = HashSet::new();
+        for root in roots {
+            Self::mark(root, &mut marked);
+        }
+
+        let unreachable: Vec<ObjectRc> = self.objects.iter()
+            .filter(|(key, _)| !marked.contains(*key))
+            .filter_map(|(_, weak)| weak.upgrade())
+            .collect();
+
+        // count the references coming from inside the unreachable set
+        let mut internal: HashMap<*const Object, usize> = HashMap::new();
+        for obj in unreachable.iter() {
+            obj.trace(&mut |child| {
+                *internal.entry(Crc::as_ptr(child)).or_insert(0) += 1;
+            });
+        }
+
+        // anything referenced from outside (minus our own upgrade) is still in use
+        for obj in unreachable.iter() {
+            let key = Crc::as_ptr(obj);
+            let from_inside = internal.get(&key).copied().unwrap_or(0);
+            if Crc::strong_count(obj) - 1 > from_inside {
+                Self::mark(obj, &mut marked);
+            }
+        }
+
+        for obj in unreachable.iter() {
+            if !marked.contains(&Crc::as_ptr(obj)) {
+                obj.unlink();
+                self.stats.cycles += 1;
+            }
+        }
+        drop(unreachable);
+
+        let before = self.objects.len();
+        self.objects.retain(|_, weak| weak.strong_count() > 0);
+        self.stats.freed += before - self.objects.len();
+
+        self.stats.collections += 1;
+        self.stats.live = self.objects.len();
+        self.next_collect = self.config.threshold.max(self.stats.live * self.config.grow_factor);
+
+        self.stats.freed - freed_before
+    }
+
+    fn mark(obj: &ObjectRc, marked: &mut HashSet<*const Object>) {
+        if !marked.insert(Crc::as_ptr(obj)) {
+            return;
+        }
+        obj.trace(&mut |child| Self::mark(child, marked));
+    }
+}
+
+// vm related
+impl LoxVM {
+
+    /**
+     * run a collection with all variables of the vm as roots
+     *
+     * ret: number of objects freed
+     */
+    pub fn gc_collect(&mut self) -> usize {
+        let roots = self.roots();
+        self.gc.collect(roots.iter())
+    }
+
+    /**
+     * track the objects created since the last poll, and collect
+     * automatically if the limit is reached
+     */
+    pub fn gc_poll(&mut self) {
+        self.gc.adopt();
+        if self.gc.should_collect() {
+            self.gc_collect();
+        }
+    }
+
+    pub fn gc_natives_register(&mut self) {
+        self.native_add("gc", Some(0), Crc::new(|vm, _| {
+            let freed = vm.gc_collect();
+            Ok(Object::Number(Number::Integer(freed as i64)).to_rc())
+        }));
+    }
+
+}
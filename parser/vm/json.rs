@@ -0,0 +1,40 @@
+use serde::Serialize;
+use serde_json::ser::{PrettyFormatter, Serializer};
+
+use crate::{
+    dbg_format,
+    types::{common::Result, object::{Object, ObjectRc}},
+    vm::vm::LoxVM,
+};
+
+
+pub fn json_parse(str: String) -> Result<ObjectRc> {
+    serde_json::from_str::<Object>(&str)
+        .map(Object::to_rc)
+        .map_err(|err| dbg_format!("json_parse: {}", err))
+}
+
+/**
+ * indent: spaces used by each level, in one line if None or 0
+ */
+pub fn json_stringify(value: ObjectRc, indent: Option<usize>) -> Result<String> {
+    let ret = match indent {
+        None | Some(0) => serde_json::to_string(value.as_ref()),
+        Some(indent) => {
+            let indent = " ".repeat(indent);
+            let mut out = Vec::new();
+            let mut ser = Serializer::with_formatter(&mut out, PrettyFormatter::with_indent(indent.as_bytes()));
+            value.serialize(&mut ser).map(|_| String::from_utf8(out).unwrap_or_default())
+        },
+    };
+    ret.map_err(|err| dbg_format!("json_stringify: {}", err))
+}
+
+impl LoxVM {
+
+    pub fn json_natives_register(&mut self) {
+        self.native_fn_add("json_parse", json_parse);
+        self.native_fn_add("json_stringify", json_stringify);
+    }
+
+}
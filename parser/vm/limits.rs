@@ -0,0 +1,152 @@
+use std::{
+    fmt::Display,
+    sync::{atomic::{AtomicBool, Ordering}, Arc},
+    time::{Duration, Instant},
+};
+
+use crate::{dbg_format, types::common::Result};
+
+use super::vm::LoxVM;
+
+
+// the clock is only read once per `DEADLINE_CHECK_STEPS` steps
+const DEADLINE_CHECK_STEPS: u64 = 256;
+
+/**
+ * execution budgets, checked for every evaluated expression and statement
+ *
+ * max_steps: maximum number of evaluated nodes in one run
+ * timeout: maximum wall-clock time of one run
+ *
+ * a run is one call of a public entry point of `LoxParser`, such as
+ * `exec_line`, `exec_code`, `exec_stmt`, `repl_feed` or `call`
+ */
+#[derive(Debug, Default, Clone)]
+pub struct VmLimits {
+    pub max_steps: Option<u64>,
+    pub timeout: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitExceeded {
+    Steps,
+    Timeout,
+    Interrupted,
+}
+
+impl Display for LimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Steps => write!(f, "StepLimitExceeded"),
+            Self::Timeout => write!(f, "Timeout"),
+            Self::Interrupted => write!(f, "Interrupted"),
+        }
+    }
+}
+
+/**
+ * stop a running vm from another thread
+ *
+ * the interrupt is consumed by the run it stops, an interrupt raised while
+ * nothing is running stops the next run
+ */
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle {
+    flag: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    pub fn interrupt(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    fn take(&self) -> bool {
+        self.flag.swap(false, Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct VmBudget {
+    pub limits: VmLimits,
+    pub interrupt: InterruptHandle,
+    pub exceeded: Option<LimitExceeded>,
+    steps: u64,
+    deadline: Option<Instant>,
+    // number of nested runs entered
+    depth: usize,
+}
+
+impl VmBudget {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /**
+     * enter a run, the counters are only reset by the outermost run,
+     * so an entry point called by another one shares its budget
+     */
+    pub fn run_enter(&mut self) {
+        if self.depth == 0 {
+            self.run_begin();
+        }
+        self.depth += 1;
+    }
+
+    pub fn run_exit(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+
+    // reset the counters
+    fn run_begin(&mut self) {
+        self.steps = 0;
+        self.deadline = self.limits.timeout.map(|timeout| Instant::now() + timeout);
+        self.exceeded = None;
+    }
+
+    fn check(&mut self) -> Option<LimitExceeded> {
+        self.steps += 1;
+        if self.interrupt.take() {
+            return Some(LimitExceeded::Interrupted);
+        }
+        if let Some(max_steps) = self.limits.max_steps {
+            if self.steps > max_steps {
+                return Some(LimitExceeded::Steps);
+            }
+        }
+        if let Some(deadline) = self.deadline {
+            if self.steps.is_multiple_of(DEADLINE_CHECK_STEPS) && Instant::now() >= deadline {
+                return Some(LimitExceeded::Timeout);
+            }
+        }
+        None
+    }
+}
+
+// vm related
+impl LoxVM {
+
+    /**
+     * count one evaluation step, fail if any budget is exceeded
+     */
+    pub fn step(&mut self) -> Result<()> {
+        match self.budget.check() {
+            None => Ok(()),
+            Some(exceeded) => {
+                self.budget.exceeded = Some(exceeded);
+                let msg = match exceeded {
+                    LimitExceeded::Steps => format!("more than {} steps evaluated",
+                        self.budget.limits.max_steps.unwrap_or_default()),
+                    LimitExceeded::Timeout => format!("execution exceeded {:?}",
+                        self.budget.limits.timeout.unwrap_or_default()),
+                    LimitExceeded::Interrupted => "execution interrupted by host".to_string(),
+                };
+                Err(dbg_format!("{}: {}", exceeded, msg))
+            },
+        }
+    }
+
+}
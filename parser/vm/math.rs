@@ -0,0 +1,153 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rust_decimal::{Decimal as ExactDecimal, RoundingStrategy};
+
+use crate::{
+    dbg_format,
+    types::{
+        common::{Crc, Result},
+        convert::{native_arg as arg, IntoLox},
+        number::Number,
+        object::{Object, ObjectRc},
+        shared_str::SharedStrExt,
+    },
+    vm::{native::NativeFnPtr, vm::LoxVM},
+};
+
+
+/**
+ * pseudo random numbers for scripts, xorshift64* seeded by splitmix64,
+ * not for cryptography
+ */
+#[derive(Debug, Clone)]
+pub struct LoxRng {
+    state: u64,
+}
+
+impl LoxRng {
+    pub fn new() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        Self::from_seed(nanos)
+    }
+
+    pub fn from_seed(seed: u64) -> Self {
+        // splitmix64, so close seeds give unrelated sequences
+        let mut zz = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        zz = (zz ^ (zz >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        zz = (zz ^ (zz >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        zz ^= zz >> 31;
+        // the state of xorshift must not be zero
+        Self { state: zz.max(1) }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /**
+     * a float in [0, 1)
+     */
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /**
+     * an integer in [low, high]
+     */
+    pub fn next_range(&mut self, low: i64, high: i64) -> i64 {
+        let range = (high as i128 - low as i128 + 1) as u128;
+        let offset = (self.next_u64() as u128 * range) >> 64;
+        (low as i128 + offset as i128) as i64
+    }
+}
+
+impl Default for LoxRng {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// functions on floats, the results are always floats
+fn float_fn(name: &'static str, op: fn(f64) -> f64) -> (&'static str, NativeFnPtr) {
+    (name, Crc::new(move |_vm, args| {
+        let num: Number = arg(name, args, 0)?;
+        Ok(Number::Decimal(op(num.to_f64())).into_lox())
+    }))
+}
+
+// min and max keep the type of the chosen number
+fn min_max(name: &'static str, args: &[ObjectRc], pick_lhs: fn(&Number, &Number) -> bool) -> Result<ObjectRc> {
+    let mut ret: Number = arg(name, args, 0)?;
+    for idx in 1..args.len() {
+        let num: Number = arg(name, args, idx)?;
+        if !pick_lhs(&ret, &num) {
+            ret = num;
+        }
+    }
+    Ok(ret.into_lox())
+}
+
+// natives related
+impl LoxVM {
+
+    pub fn math_natives_register(&mut self) {
+        self.global_add("PI".to_string().to_share(), Number::Decimal(std::f64::consts::PI).into_lox());
+        self.global_add("E".to_string().to_share(), Number::Decimal(std::f64::consts::E).into_lox());
+
+        let float_fns = [
+            float_fn("sqrt", f64::sqrt),
+            float_fn("sin", f64::sin),
+            float_fn("cos", f64::cos),
+            float_fn("tan", f64::tan),
+            float_fn("log", f64::ln),
+            float_fn("exp", f64::exp),
+        ];
+        for (name, func) in float_fns {
+            self.native_add(name, Some(1), func);
+        }
+
+        self.native_add("abs", Some(1), Crc::new(|vm, args| {
+            let num: Number = arg("abs", args, 0)?;
+            Ok(num.abs_ref(vm.overflow)?.into_lox())
+        }));
+        self.native_add("pow", Some(2), Crc::new(|vm, args| {
+            let base: Number = arg("pow", args, 0)?;
+            let exp: Number = arg("pow", args, 1)?;
+            Ok(base.pow_ref(&exp, vm.overflow)?.into_lox())
+        }));
+        self.native_fn_add("floor", |num: Number| num.round_with(f64::floor, ExactDecimal::floor));
+        self.native_fn_add("ceil", |num: Number| num.round_with(f64::ceil, ExactDecimal::ceil));
+        // halves are rounded away from zero
+        self.native_fn_add("round", |num: Number| num.round_with(f64::round,
+            |exact| exact.round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero)));
+
+        self.native_add("min", None, Crc::new(|_vm, args| min_max("min", args, |lhs, rhs| lhs <= rhs)));
+        self.native_add("max", None, Crc::new(|_vm, args| min_max("max", args, |lhs, rhs| lhs >= rhs)));
+
+        // random(): a float in [0, 1)
+        self.native_add("random", Some(0), Crc::new(|vm, _args| {
+            Ok(Number::Decimal(vm.rng.next_f64()).into_lox())
+        }));
+        // random_int(low, high): an integer in [low, high]
+        self.native_add("random_int", Some(2), Crc::new(|vm, args| {
+            let low: i64 = arg("random_int", args, 0)?;
+            let high: i64 = arg("random_int", args, 1)?;
+            if low > high {
+                return Err(dbg_format!("random_int: empty range [{}, {}]", low, high));
+            }
+            Ok(vm.rng.next_range(low, high).into_lox())
+        }));
+        // random_seed(seed): restart the sequence, the same seed gives the same numbers
+        self.native_add("random_seed", Some(1), Crc::new(|vm, args| {
+            let seed: i64 = arg("random_seed", args, 0)?;
+            vm.rng = LoxRng::from_seed(seed as u64);
+            Ok(Object::Nil.to_rc())
+        }));
+    }
+
+}
@@ -0,0 +1,85 @@
+use std::fmt::Debug;
+
+use crate::{
+    dbg_format,
+    types::{
+        common::{Crc, Result}, convert::IntoNative, object::{Object, ObjectRc}, shared_str::{SharedStr, SharedStrExt}
+    },
+    vm::vm::LoxVM,
+};
+
+
+pub type NativeFnPtr = Crc<dyn Fn(&mut LoxVM, &[ObjectRc]) -> Result<ObjectRc>>;
+
+/**
+ * a function implemented in rust and callable from lox scripts
+ *
+ * arity: None for variadic functions
+ */
+#[derive(Clone)]
+pub struct NativeFn {
+    name: SharedStr,
+    arity: Option<usize>,
+    func: NativeFnPtr,
+}
+
+impl NativeFn {
+    pub fn new(name: &str, arity: Option<usize>, func: NativeFnPtr) -> Self {
+        Self {
+            name: name.to_string().to_share(),
+            arity,
+            func,
+        }
+    }
+
+    pub fn name(&self) -> &SharedStr {
+        &self.name
+    }
+
+    pub fn arity(&self) -> Option<usize> {
+        self.arity
+    }
+
+    pub fn call(&self, vm: &mut LoxVM, args: &[ObjectRc]) -> Result<ObjectRc> {
+        if let Some(arity) = self.arity {
+            if arity != args.len() {
+                return Err(dbg_format!("function `{}` expect {} arguments, got {}", self.name, arity, args.len()));
+            }
+        }
+        let ret = (self.func)(vm, args)?;
+        // new objects come from natives, so the collector is polled here
+        vm.gc_poll();
+        Ok(ret)
+    }
+}
+
+impl Debug for NativeFn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+// register helpers
+impl LoxVM {
+
+    /**
+     * register a native function as a global variable
+     *
+     * name: name used by scripts to call the function
+     * arity: number of arguments, None for variadic
+     * func: the implementation
+     */
+    pub fn native_add(&mut self, name: &str, arity: Option<usize>, func: NativeFnPtr) {
+        let native = NativeFn::new(name, arity, func);
+        self.global_add(native.name().clone(), Object::Native(native).to_rc());
+    }
+
+    /**
+     * register a rust function with typed arguments as a native function
+     */
+    pub fn native_fn_add<Args, F: IntoNative<Args>>(&mut self, name: &str, func: F) {
+        let ptr = func.into_native(name);
+        self.native_add(name, Some(F::arity()), ptr);
+    }
+
+}
@@ -0,0 +1,34 @@
+use crate::{
+    dbg_format,
+    types::{common::Result, number::Number},
+    vm::vm::LoxVM,
+};
+
+
+/**
+ * to_float(num): the number as f64
+ */
+pub fn to_float(num: Number) -> Number {
+    Number::Decimal(num.to_f64())
+}
+
+/**
+ * to_exact(num): the number as an exact decimal,
+ * floats are converted by their shortest representation
+ */
+pub fn to_exact(num: Number) -> Result<Number> {
+    match num.to_exact() {
+        Some(exact) => Ok(Number::Exact(exact)),
+        None => Err(dbg_format!("cannot convert {} to exact decimal", num)),
+    }
+}
+
+// natives related
+impl LoxVM {
+
+    pub fn numeric_natives_register(&mut self) {
+        self.native_fn_add("to_float", to_float);
+        self.native_fn_add("to_exact", to_exact);
+    }
+
+}
@@ -0,0 +1,120 @@
+use std::collections::{BTreeMap, HashMap};
+
+use regex::{Captures, Regex};
+
+use crate::{
+    dbg_format,
+    types::{
+        common::{Crc, Result},
+        convert::{native_arg as arg, IntoLox},
+        object::{Object, ObjectRc},
+        shared_str::SharedStrExt,
+    },
+    vm::vm::LoxVM,
+};
+
+
+// the cache is dropped when it's full, scripts rarely use this many patterns
+const PATTERN_CACHE_MAX: usize = 128;
+
+/**
+ * compiled regex patterns, so scripts can use a pattern in a loop
+ * without compiling it on every call
+ */
+#[derive(Debug, Default)]
+pub struct PatternCache {
+    patterns: HashMap<String, Regex>,
+}
+
+impl PatternCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&mut self, pattern: &str) -> Result<Regex> {
+        if let Some(re) = self.patterns.get(pattern) {
+            return Ok(re.clone());
+        }
+        let re = Regex::new(pattern).map_err(|err| dbg_format!("invalid pattern: {}", err))?;
+        if self.patterns.len() >= PATTERN_CACHE_MAX {
+            self.patterns.clear();
+        }
+        self.patterns.insert(pattern.to_string(), re.clone());
+        Ok(re)
+    }
+
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.patterns.clear();
+    }
+}
+
+/**
+ * the groups of a match, a map from the group names if the pattern
+ * has named groups, a list of all groups otherwise.
+ * groups not taking part in the match are nil
+ */
+fn captures_to_lox(re: &Regex, caps: &Captures) -> ObjectRc {
+    let text = |idx: usize| caps.get(idx).map(|mat| mat.as_str()).into_lox();
+    let named = re.capture_names()
+        .enumerate()
+        .filter_map(|(idx, name)| name.map(|name| (name.to_string().to_share(), text(idx))))
+        .collect::<BTreeMap<_, _>>();
+    match named.is_empty() {
+        true => Object::new_list((0..caps.len()).map(text).collect()).to_rc(),
+        false => Object::new_map(named).to_rc(),
+    }
+}
+
+// natives related
+impl LoxVM {
+
+    pub fn pattern_natives_register(&mut self) {
+        // re_match(pattern, str): groups of the first match, nil if not matched
+        self.native_add("re_match", Some(2), Crc::new(|vm, args| {
+            let re = vm.patterns.get(&arg::<String>("re_match", args, 0)?)?;
+            let str: String = arg("re_match", args, 1)?;
+            Ok(match re.captures(&str) {
+                Some(caps) => captures_to_lox(&re, &caps),
+                None => Object::Nil.to_rc(),
+            })
+        }));
+
+        // re_find_all(pattern, str): all matched strings,
+        // or the groups of each match if the pattern has groups
+        self.native_add("re_find_all", Some(2), Crc::new(|vm, args| {
+            let re = vm.patterns.get(&arg::<String>("re_find_all", args, 0)?)?;
+            let str: String = arg("re_find_all", args, 1)?;
+            let found: Vec<ObjectRc> = match re.captures_len() {
+                1 => re.find_iter(&str).map(|mat| mat.as_str().into_lox()).collect(),
+                _ => re.captures_iter(&str).map(|caps| captures_to_lox(&re, &caps)).collect(),
+            };
+            Ok(found.into_lox())
+        }));
+
+        // re_replace(pattern, str, replacement): replace all matches,
+        // `$1' and `$name' in the replacement refer to groups, write
+        // `\${name}' to keep it from string interpolation
+        self.native_add("re_replace", Some(3), Crc::new(|vm, args| {
+            let re = vm.patterns.get(&arg::<String>("re_replace", args, 0)?)?;
+            let str: String = arg("re_replace", args, 1)?;
+            let rep: String = arg("re_replace", args, 2)?;
+            Ok(re.replace_all(&str, rep.as_str()).into_owned().into_lox())
+        }));
+
+        // re_split(pattern, str): the strings between the matches
+        self.native_add("re_split", Some(2), Crc::new(|vm, args| {
+            let re = vm.patterns.get(&arg::<String>("re_split", args, 0)?)?;
+            let str: String = arg("re_split", args, 1)?;
+            Ok(re.split(&str).collect::<Vec<_>>().into_lox())
+        }));
+    }
+
+}
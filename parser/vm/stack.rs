@@ -8,7 +8,6 @@ use crate::{dbg_format,
 
 
 #[derive(Debug)]
-#[allow(dead_code)]
 pub struct VmStack {
     name: SharedStr,
     scopes: Vec<VmVarPool>,
@@ -26,25 +25,32 @@ impl VmStack {
         stack
     }
 
+    pub fn name(&self) -> &SharedStr {
+        &self.name
+    }
+
     pub fn clear(&mut self) {
-        self.scopes.clear()
+        self.scopes.clear();
+        // keep the default scope
+        self.scope_enter();
     }
 
+    // the innermost scope is kept at the end, so enter/exit are O(1)
     pub fn scope_enter(&mut self) {
-        self.scopes.insert(0, VmVarPool::new())
+        self.scopes.push(VmVarPool::new())
     }
 
     pub fn scope_exit(&mut self) {
-        self.scopes.remove(0);
+        self.scopes.pop();
     }
 
     pub fn scope_current(&mut self) -> &mut VmVarPool {
         // should always get a scope
-        self.scopes.get_mut(0).unwrap()
+        self.scopes.last_mut().unwrap()
     }
 
     pub fn scope_of_var(&self, name: &SharedStr) -> Result<&VmVarPool> {
-        let mut iter = self.scopes.iter();
+        let mut iter = self.scopes.iter().rev();
         while let Some(scope) = iter.next() {
             if scope.var_exist(name) {
                 return Ok(scope);
@@ -54,7 +60,7 @@ impl VmStack {
     }
 
     pub fn scope_of_var_mut(&mut self, name: &SharedStr) -> Result<&mut VmVarPool> {
-        let mut iter = self.scopes.iter_mut();
+        let mut iter = self.scopes.iter_mut().rev();
         while let Some(scope) = iter.next() {
             if scope.var_exist(name) {
                 return Ok(scope);
@@ -105,7 +111,22 @@ impl VmStack {
     }
 
     pub fn var_exist(&self, name: &SharedStr) -> bool {
-        self.scope_of_var(name).is_ok()
+        // checked on every stack during lookup, don't build the error message
+        self.scopes.iter().any(|scope| scope.var_exist(name))
+    }
+
+    /**
+     * iterate all variable names of every scope
+     */
+    pub fn names(&self) -> impl Iterator<Item = &SharedStr> {
+        self.scopes.iter().flat_map(|scope| scope.names())
+    }
+
+    /**
+     * iterate all variable values of every scope
+     */
+    pub fn values(&self) -> impl Iterator<Item = &ObjectRc> {
+        self.scopes.iter().flat_map(|scope| scope.values())
     }
 
 }
@@ -0,0 +1,170 @@
+use crate::{
+    dbg_format,
+    types::{
+        common::{Crc, Result},
+        convert::{native_arg as arg, FromLox, IntoLox},
+        object::{Object, ObjectRc},
+    },
+    vm::vm::LoxVM,
+};
+
+
+// largest string made by `repeat', in bytes
+pub const REPEAT_BYTES_MAX: usize = 1 << 26;
+
+/*
+ * strings are indexed by characters, not bytes,
+ * negative indexes count from the end
+ */
+
+// position of `idx' in a sequence of `len' items, None if out of range
+pub fn position(idx: i64, len: usize) -> Option<usize> {
+    let pos = if idx < 0 { idx + len as i64 } else { idx };
+    (0..len as i64).contains(&pos).then_some(pos as usize)
+}
+
+// range of a slice, the bounds are clamped to the sequence
+fn range(start: Option<i64>, end: Option<i64>, len: usize) -> (usize, usize) {
+    let clamp = |idx: i64| {
+        let pos = if idx < 0 { idx + len as i64 } else { idx };
+        pos.clamp(0, len as i64) as usize
+    };
+    let start = start.map_or(0, clamp);
+    let end = end.map_or(len, clamp);
+    (start, end.max(start))
+}
+
+pub fn index_of(idx: &ObjectRc) -> Result<i64> {
+    i64::from_lox(idx).map_err(|err| dbg_format!("bad index: {}", err))
+}
+
+/**
+ * obj[idx]: a character of a string, an item of a list,
+ * or the value of a key in a map (nil if not exists)
+ */
+pub fn index(obj: &ObjectRc, idx: &ObjectRc) -> Result<ObjectRc> {
+    match obj.as_ref() {
+        Object::String(str) => {
+            let pos = index_of(idx)?;
+            position(pos, str.chars().count())
+                .and_then(|pos| str.chars().nth(pos))
+                .map(|ch| ch.to_string().into_lox())
+                .ok_or_else(|| dbg_format!("string index {} out of range", pos))
+        },
+        Object::List(list) => {
+            let pos = index_of(idx)?;
+            let list = list.borrow();
+            position(pos, list.len())
+                .map(|pos| list[pos].clone())
+                .ok_or_else(|| dbg_format!("list index {} out of range", pos))
+        },
+        Object::Map(map) => match idx.as_ref() {
+            Object::String(key) => Ok(map.borrow().get(key).cloned().unwrap_or_else(|| Object::Nil.to_rc())),
+            key => Err(dbg_format!("map key must be a string, got {}", key.type_name())),
+        },
+        obj => Err(dbg_format!("cannot index {}", obj.type_name())),
+    }
+}
+
+/**
+ * obj[start:end]: part of a string or a list, the end is not included
+ */
+pub fn slice(obj: &ObjectRc, start: Option<i64>, end: Option<i64>) -> Result<ObjectRc> {
+    match obj.as_ref() {
+        Object::String(str) => {
+            let (start, end) = range(start, end, str.chars().count());
+            Ok(str.chars().skip(start).take(end - start).collect::<String>().into_lox())
+        },
+        Object::List(list) => {
+            let list = list.borrow();
+            let (start, end) = range(start, end, list.len());
+            Ok(Object::new_list(list[start..end].to_vec()).to_rc())
+        },
+        obj => Err(dbg_format!("cannot slice {}", obj.type_name())),
+    }
+}
+
+// builtins which are methods of strings, called as `str.name(args)'
+pub const METHODS: [&str; 12] = ["len", "upper", "lower", "trim", "starts_with", "ends_with",
+    "replace", "split", "contains", "find", "repeat", "substr"];
+
+// natives related
+impl LoxVM {
+
+    pub fn string_natives_register(&mut self) {
+        // len(obj): characters of a string, or items of a list or a map
+        self.native_add("len", Some(1), Crc::new(|_vm, args| {
+            let len = match args[0].as_ref() {
+                Object::String(str) => str.chars().count(),
+                Object::List(list) => list.borrow().len(),
+                Object::Map(map) => map.borrow().len(),
+                obj => return Err(dbg_format!("len: {} has no length", obj.type_name())),
+            };
+            Ok((len as i64).into_lox())
+        }));
+
+        self.native_fn_add("upper", |str: String| str.to_uppercase());
+        self.native_fn_add("lower", |str: String| str.to_lowercase());
+        self.native_fn_add("trim", |str: String| str.trim().to_string());
+        self.native_fn_add("starts_with", |str: String, prefix: String| str.starts_with(&prefix));
+        self.native_fn_add("ends_with", |str: String, suffix: String| str.ends_with(&suffix));
+        self.native_fn_add("replace", |str: String, from: String, to: String| str.replace(&from, &to));
+
+        // split(str, sep): an empty separator splits the string into characters
+        self.native_fn_add("split", |str: String, sep: String| -> Vec<String> {
+            match sep.is_empty() {
+                true => str.chars().map(String::from).collect(),
+                false => str.split(&sep).map(String::from).collect(),
+            }
+        });
+        // join(list, sep): the items are printed as by `print'
+        self.native_fn_add("join", |list: Vec<ObjectRc>, sep: String| {
+            list.iter().map(|item| item.to_string()).collect::<Vec<_>>().join(&sep)
+        });
+
+        // contains(obj, item): substring of a string, or item of a list
+        self.native_add("contains", Some(2), Crc::new(|_vm, args| {
+            match args[0].as_ref() {
+                Object::List(list) => {
+                    for item in list.borrow().iter() {
+                        if item.eq(&args[1])?.is_true()? {
+                            return Ok(true.into_lox());
+                        }
+                    }
+                    Ok(false.into_lox())
+                },
+                _ => {
+                    let str: String = arg("contains", args, 0)?;
+                    let sub: String = arg("contains", args, 1)?;
+                    Ok(str.contains(&sub).into_lox())
+                },
+            }
+        }));
+
+        // find(str, sub): character index of the first `sub', nil if not found
+        self.native_fn_add("find", |str: String, sub: String| {
+            str.find(&sub).map(|pos| str[..pos].chars().count() as i64)
+        });
+
+        self.native_add("repeat", Some(2), Crc::new(|_vm, args| {
+            let str: String = arg("repeat", args, 0)?;
+            let count: i64 = arg("repeat", args, 1)?;
+            if count < 0 {
+                return Err(dbg_format!("repeat: negative count {}", count));
+            }
+            match str.len().checked_mul(count as usize) {
+                Some(bytes) if bytes <= REPEAT_BYTES_MAX => Ok(str.repeat(count as usize).into_lox()),
+                _ => Err(dbg_format!("repeat: result longer than {} bytes", REPEAT_BYTES_MAX)),
+            }
+        }));
+
+        // substr(str, start, end): same as str[start:end]
+        self.native_add("substr", Some(3), Crc::new(|_vm, args| {
+            let _: String = arg("substr", args, 0)?;
+            let start: i64 = arg("substr", args, 1)?;
+            let end: i64 = arg("substr", args, 2)?;
+            slice(&args[0], Some(start), Some(end))
+        }));
+    }
+
+}
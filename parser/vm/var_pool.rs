@@ -86,4 +86,12 @@ impl VmVarPool {
         self.pool.contains_key(name)
     }
 
+    pub fn names(&self) -> impl Iterator<Item = &SharedStr> {
+        self.pool.keys()
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &ObjectRc> {
+        self.pool.values()
+    }
+
 }
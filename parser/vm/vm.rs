@@ -1,13 +1,15 @@
 
 
+use std::collections::HashMap;
+
 use crate::{
     dbg_format,
     syntax::{
-            expression::Expr,
+            expression::{Expr, StrPart},
             statement::Stmt,
             token::Token
         }, types::{
-            common::Result, object::{
+            common::{Crc, Result}, convert::{FromLox, IntoLox}, number::{DecimalMode, OverflowMode}, object::{
                 Object,
                 ObjectRc,
             },
@@ -15,23 +17,73 @@ use crate::{
                 SharedStr,
                 SharedStrExt
             },
-        }, vm::{console::LoxPrinter, stack::VmStack},
+        }, vm::{console::LoxPrinter, container, gc::LoxGc, limits::VmBudget, math::LoxRng, pattern::PatternCache, stack::VmStack, string},
 };
 
+// default limit of nested function calls
+pub const CALL_DEPTH_MAX: usize = 1024;
+
+// function bodies are executed on a new stack segment when the rust stack
+// has less than `STACK_RED_ZONE` left, so deep recursion ends with the lox
+// `StackOverflow' error instead of overflowing the host stack
+const STACK_RED_ZONE: usize = 128 * 1024;
+const STACK_GROW_SIZE: usize = 2 * 1024 * 1024;
+
 #[derive(Debug)]
 pub struct LoxVM {
     global: VmStack,
     stacks: Vec<VmStack>,
+    call_depth_max: usize,
+    // value of the executed `return', blocks and loops stop when it's set
+    returning: Option<ObjectRc>,
+    pub overflow: OverflowMode,
+    pub decimal: DecimalMode,
     pub printer: LoxPrinter,
+    pub gc: LoxGc,
+    pub budget: VmBudget,
+    pub patterns: PatternCache,
+    pub rng: LoxRng,
+    // globals defined by `natives_register'
+    builtins: HashMap<String, ObjectRc>,
 }
 
 impl LoxVM {
     pub fn new () -> Self {
-        Self {
+        let mut vm = Self {
             global: VmStack::new("(global)".to_string().to_share()),
             stacks: Vec::new(),
+            call_depth_max: CALL_DEPTH_MAX,
+            returning: None,
+            overflow: OverflowMode::default(),
+            decimal: DecimalMode::default(),
             printer: LoxPrinter::new(),
-        }
+            gc: LoxGc::new(),
+            budget: VmBudget::new(),
+            patterns: PatternCache::new(),
+            rng: LoxRng::new(),
+            builtins: HashMap::new(),
+        };
+        vm.natives_register();
+        vm
+    }
+
+    fn natives_register(&mut self) {
+        self.gc_natives_register();
+        self.container_natives_register();
+        self.json_natives_register();
+        self.pattern_natives_register();
+        self.numeric_natives_register();
+        self.format_natives_register();
+        self.math_natives_register();
+        self.string_natives_register();
+        self.builtins = self.global_vars().into_iter().collect();
+    }
+
+    /**
+     * check if the global is a builtin not redefined by the script
+     */
+    pub fn is_builtin(&self, name: &str, value: &ObjectRc) -> bool {
+        self.builtins.get(name).is_some_and(|builtin| Crc::ptr_eq(builtin, value))
     }
 }
 
@@ -40,6 +92,7 @@ impl LoxVM {
 impl LoxVM {
 
     pub fn eval(&mut self, expr: &Expr) -> Result<ObjectRc> {
+        self.step()?;
         use Expr::*;
         use Token::{*};
         match expr {
@@ -52,14 +105,29 @@ impl LoxVM {
             Literal(Identifier(idnt_name)) => Ok(self.var_get(idnt_name)?),
             // Unary expr
             Unary(Bang, expr) => self.eval(expr)?.not_rc(),
-            Unary(Minus, expr) => self.eval(expr)?.neg_rc(),
+            Unary(Minus, expr) => self.eval(expr)?.neg_rc(self.overflow),
             // Group expr
             Group(expr) => self.eval(expr),
             // Binary
-            Binary(left, Slash, right) => self.eval(left)?.div_rc(self.eval(right)?),
-            Binary(left, Star, right) => self.eval(left)?.mul_rc(self.eval(right)?),
-            Binary(left, Minus, right) => self.eval(left)?.sub_rc(self.eval(right)?),
-            Binary(left, Plus, right) => self.eval(left)?.add_rc(self.eval(right)?),
+            // the right side and the branches are evaluated only when used
+            Binary(left, QuestionQuestion, right) => {
+                let value = self.eval(left)?;
+                match value.as_ref() {
+                    Object::Nil => self.eval(right),
+                    _ => Ok(value),
+                }
+            },
+            Ternary(cond, then, other) => {
+                match self.eval(cond)?.is_true()? {
+                    true => self.eval(then),
+                    false => self.eval(other),
+                }
+            },
+            Binary(left, Percent, right) => self.eval(left)?.rem_rc(self.eval(right)?, self.overflow),
+            Binary(left, Slash, right) => self.eval(left)?.div_rc(self.eval(right)?, self.overflow),
+            Binary(left, Star, right) => self.eval(left)?.mul_rc(self.eval(right)?, self.overflow),
+            Binary(left, Minus, right) => self.eval(left)?.sub_rc(self.eval(right)?, self.overflow),
+            Binary(left, Plus, right) => self.eval(left)?.add_rc(self.eval(right)?, self.overflow),
             Binary(left, Greater, right) => self.eval(left)?.gt_rc(self.eval(right)?),
             Binary(left, GreaterEqual, right) => self.eval(left)?.ge_rc(self.eval(right)?),
             Binary(left, Less, right) => self.eval(left)?.lt_rc(self.eval(right)?),
@@ -72,30 +140,64 @@ impl LoxVM {
                 let value = self.eval(expr)?;
                 self.var_set(idnt_name.clone(), value)
             },
+            // the variable is read and written only once
+            Compound(Identifier(idnt_name), op, expr) => {
+                let current = self.var_get(idnt_name)?;
+                let rhs = self.eval(expr)?;
+                let value = self.binary_op(&current, op, rhs)?;
+                self.var_set(idnt_name.clone(), value)
+            },
+            Incr(Identifier(idnt_name), op, prefix) => {
+                let current = self.var_get(idnt_name)?;
+                if !matches!(current.as_ref(), Object::Number(_)) {
+                    return Err(dbg_format!("cannot increase or decrease {} `{}`", current.type_name(), idnt_name));
+                }
+                let value = self.binary_op(&current, op, 1.into_lox())?;
+                self.var_set(idnt_name.clone(), value.clone())?;
+                Ok(if *prefix { value } else { current })
+            },
             FnCall(fn_name, args) => {
-
                 let fn_obj = self.var_get(fn_name)?;
-                let (params, body) = match fn_obj.as_ref() {
-                    Object::Function(params, body) => (params, body),
-                    _ => return Err(dbg_format!("not a function: {}", fn_name)),
+                // arguments belong to the caller, evaluate before entering the new stack
+                let arg_objs = self.eval_args(args)?;
+                self.call(fn_name, &fn_obj, arg_objs)
+            },
+            Index(obj, idx) => {
+                let obj = self.eval(obj)?;
+                string::index(&obj, &self.eval(idx)?)
+            },
+            Slice(obj, start, end) => {
+                let obj = self.eval(obj)?;
+                let start = self.eval_bound(start)?;
+                let end = self.eval_bound(end)?;
+                string::slice(&obj, start, end)
+            },
+            Method(recv, name, args) => {
+                let recv = self.eval(recv)?;
+                // a method of the type is the builtin, even if the script redefined the global,
+                // any other function defined by the host or the script can be used as a method
+                let methods: &[&str] = match recv.as_ref() {
+                    Object::String(_) => &string::METHODS,
+                    Object::List(_) => &container::LIST_METHODS,
+                    Object::Map(_) => &container::MAP_METHODS,
+                    _ => &[],
                 };
-
-                let n_params = params.len();
-                if n_params != args.len() {
-                    return Err(dbg_format!("function `{}` expect {} arguments, got {}", fn_name, n_params, args.len()));
-                }
-
-                self.stack_new(fn_name.clone());
-                for i in 0..n_params {
-                    let arg = self.eval(args.get(i).unwrap())?;
-                    let arg_name = params.get(i).unwrap().clone();
-                    self.var_add(arg_name, arg);
-                }
-
-                let ret = self.exec(&body)?;
-                self.stack_del();
-                Ok(ret)
+                let fn_obj = match methods.contains(&name.as_ref()) {
+                    true => self.builtins.get(name.as_ref()).cloned(),
+                    false => self.var_get(name).ok().filter(|fn_obj| {
+                        matches!(fn_obj.as_ref(), Object::Function(_) | Object::Native(_))
+                            && !self.is_builtin(name, fn_obj)
+                    }),
+                };
+                let fn_obj = match fn_obj {
+                    Some(fn_obj) => fn_obj,
+                    None => return Err(dbg_format!("{} has no method `{}`", recv.type_name(), name)),
+                };
+                let mut arg_objs = vec![recv];
+                arg_objs.extend(self.eval_args(args)?);
+                self.call(name, &fn_obj, arg_objs)
             },
+            Expr::Interp(parts) => self.eval_interp(parts),
             left => {
                 Err(dbg_format!("NOT CHECKED TYPE: {:#?}", left))
             },
@@ -103,6 +205,7 @@ impl LoxVM {
     }
 
     pub fn exec(&mut self, stmt: &Stmt) -> Result<ObjectRc> {
+        self.step()?;
         match stmt {
             Stmt::Expr(expr) => {
                 self.eval(expr)?;
@@ -113,11 +216,9 @@ impl LoxVM {
             },
             Stmt::Block(stmts) => {
                 self.block_enter();
-                let mut iter = stmts.iter();
-                while let Some(stmt) = iter.next() {
-                    self.exec(stmt)?;
-                }
+                let ret = self.exec_all(stmts);
                 self.block_exit();
+                ret?;
             }
             Stmt::If(cont, stmt_true, opt_false) => {
                 if self.eval(cont)?.is_true()? {
@@ -137,32 +238,32 @@ impl LoxVM {
                     },
                 };
             },
-            Stmt::FunDecl(fn_name, params, fn_body) => {
-                self.var_add(fn_name.clone(), Object::Function(params.clone(), *fn_body.clone()).to_rc());
+            Stmt::FunDecl(fun) => {
+                self.var_add(fun.name.clone(), Object::Function(fun.clone()).to_rc());
             },
             Stmt::While(cont, body) => {
                 while self.eval(cont)?.is_true()? {
                     self.exec(body)?;
+                    if self.returning.is_some() {
+                        break;
+                    }
                 }
             },
             Stmt::For(start, cont, every, body) => {
                 self.block_enter();
-                if let Some(start) = start {
-                    self.exec(start)?;
-                }
-                loop {
-                    if let Some(cont) = cont {
-                        if !self.eval(cont)?.is_true()? {
-                            break;
-                        }
-                    }
-                    self.exec(body)?;
-
-                    if let Some(every) = every {
-                        self.eval(every)?;
-                    }
-                }
+                let ret = self.exec_for(start, cont, every, body);
                 self.block_exit();
+                ret?;
+            },
+            Stmt::Return(expr) => {
+                if self.stacks.is_empty() {
+                    return Err(dbg_format!("cannot return from top-level code"));
+                }
+                let value = match expr {
+                    Some(expr) => self.eval(expr)?,
+                    None => Object::Nil.to_rc(),
+                };
+                self.returning = Some(value);
             },
             _ => {
                 return Err(dbg_format!("Unexpected statement"));
@@ -171,6 +272,118 @@ impl LoxVM {
         Ok(Object::Nil.to_rc())
     }
 
+    /**
+     * call a function object
+     *
+     * name: name of the new stack, shown in the call trace
+     * fn_obj: a lox function or a native function
+     * args: evaluated arguments
+     *
+     * ret: the returned value, Nil if the function doesn't return any
+     */
+    pub fn call(&mut self, name: &SharedStr, fn_obj: &ObjectRc, args: Vec<ObjectRc>) -> Result<ObjectRc> {
+        let fun = match fn_obj.as_ref() {
+            Object::Function(fun) => fun,
+            Object::Native(native) => return native.call(self, &args),
+            _ => return Err(dbg_format!("not a function: {}", name)),
+        };
+
+        let params = &fun.params;
+        if params.len() != args.len() {
+            return Err(dbg_format!("function `{}` expect {} arguments, got {}", name, params.len(), args.len()));
+        }
+        if self.stacks.len() >= self.call_depth_max {
+            return Err(self.stack_overflow_error());
+        }
+
+        self.stack_new_with_args(name.clone(), params.clone(), args);
+        let ret = stacker::maybe_grow(STACK_RED_ZONE, STACK_GROW_SIZE, || {
+            self.exec(&fun.body)
+        });
+        self.stack_del();
+        let value = self.returning.take();
+        ret?;
+        Ok(value.unwrap_or_else(|| Object::Nil.to_rc()))
+    }
+
+    fn exec_all(&mut self, stmts: &[Stmt]) -> Result<()> {
+        for stmt in stmts.iter() {
+            self.exec(stmt)?;
+            if self.returning.is_some() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn eval_args(&mut self, args: &[Expr]) -> Result<Vec<ObjectRc>> {
+        let mut arg_objs = Vec::with_capacity(args.len());
+        for arg in args.iter() {
+            arg_objs.push(self.eval(arg)?);
+        }
+        Ok(arg_objs)
+    }
+
+    // arithmetic of compound assignments
+    fn binary_op(&self, lhs: &ObjectRc, op: &Token, rhs: ObjectRc) -> Result<ObjectRc> {
+        match op {
+            Token::Plus => lhs.add_rc(rhs, self.overflow),
+            Token::Minus => lhs.sub_rc(rhs, self.overflow),
+            Token::Star => lhs.mul_rc(rhs, self.overflow),
+            Token::Slash => lhs.div_rc(rhs, self.overflow),
+            Token::Percent => lhs.rem_rc(rhs, self.overflow),
+            op => Err(dbg_format!("not an arithmetic operator: {:#?}", op)),
+        }
+    }
+
+    // embedded values are printed as by `print'
+    fn eval_interp(&mut self, parts: &[StrPart]) -> Result<ObjectRc> {
+        let mut str = String::new();
+        for part in parts {
+            match part {
+                StrPart::Text(text) => str.push_str(text),
+                StrPart::Embed { expr, source, column } => {
+                    let obj = self.eval(expr).map_err(|err| StrPart::error(source, *column, &err))?;
+                    str.push_str(&obj.to_string());
+                },
+            }
+        }
+        Ok(Object::new_string(str).to_rc())
+    }
+
+    // bound of a slice, must be an integer if given
+    fn eval_bound(&mut self, bound: &Option<Box<Expr>>) -> Result<Option<i64>> {
+        match bound {
+            Some(expr) => {
+                let obj = self.eval(expr)?;
+                i64::from_lox(&obj).map(Some).map_err(|err| dbg_format!("bad slice bound: {}", err))
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn exec_for(&mut self, start: &Option<Box<Stmt>>, cont: &Option<Expr>, every: &Option<Expr>, body: &Stmt) -> Result<()> {
+        if let Some(start) = start {
+            self.exec(start)?;
+        }
+        loop {
+            if let Some(cont) = cont {
+                if !self.eval(cont)?.is_true()? {
+                    break;
+                }
+            }
+            self.exec(body)?;
+            if self.returning.is_some() {
+                break;
+            }
+
+            if let Some(every) = every {
+                self.eval(every)?;
+            }
+        }
+        Ok(())
+    }
+
 }
 
 
@@ -181,21 +394,83 @@ impl LoxVM {
     pub fn clear(&mut self) {
         self.global.clear();
         self.stacks.clear();
+        self.returning = None;
+        self.gc.clear();
+        self.patterns.clear();
+        self.natives_register();
+    }
+
+    /**
+     * names of all variables defined in global
+     */
+    pub fn global_names(&self) -> Vec<String> {
+        self.global.names().map(|name| name.to_string()).collect()
+    }
+
+    pub fn global_get(&self, name: &SharedStr) -> Option<ObjectRc> {
+        self.global.var_get(name).ok()
+    }
+
+    /**
+     * all variables defined in global, with their values
+     */
+    pub fn global_vars(&self) -> Vec<(String, ObjectRc)> {
+        self.global.names()
+            .filter_map(|name| Some((name.to_string(), self.global.var_get(name).ok()?)))
+            .collect()
+    }
+
+    /**
+     * collect all variables of every stack, used as the roots of gc
+     */
+    pub fn roots(&self) -> Vec<ObjectRc> {
+        let mut roots: Vec<ObjectRc> = self.global.values().cloned().collect();
+        for stack in self.stacks.iter() {
+            roots.extend(stack.values().cloned());
+        }
+        roots
+    }
+
+    pub fn call_depth_max(&self) -> usize {
+        self.call_depth_max
+    }
+
+    pub fn call_depth_max_set(&mut self, depth: usize) {
+        self.call_depth_max = depth;
+    }
+
+    /**
+     * build the `StackOverflow' error with the lox call trace,
+     * repeated frames (recursion) are folded into one line
+     */
+    fn stack_overflow_error(&self) -> String {
+        let mut trace = String::new();
+        let mut iter = self.stacks.iter().rev().map(|stack| stack.name()).peekable();
+        while let Some(name) = iter.next() {
+            let mut count = 1;
+            while iter.next_if(|next| *next == name).is_some() {
+                count += 1;
+            }
+            match count {
+                1 => trace.push_str(&format!("\n    at {}", name)),
+                _ => trace.push_str(&format!("\n    at {} (repeated {} times)", name, count)),
+            }
+        }
+        dbg_format!("StackOverflow: maximum call depth {} exceeded{}", self.call_depth_max, trace)
     }
 
+    // the newest stack is kept at the end, so new/del are O(1)
     pub fn stack_new(&mut self, name: SharedStr) {
-        self.stacks.insert(0, VmStack::new(name))
+        self.stacks.push(VmStack::new(name))
     }
 
-    #[allow(dead_code)]
     pub fn stack_new_with_args(&mut self, stack_name: SharedStr, params: Vec<SharedStr>, args: Vec<ObjectRc>) {
         self.stack_new(stack_name);
         self.var_add_all(params, args);
     }
 
     pub fn stack_del(&mut self) {
-        // self.env.borrow_mut().stacks.remove(0);
-        self.stacks.remove(0);
+        self.stacks.pop();
     }
 
     /**
@@ -203,23 +478,21 @@ impl LoxVM {
      */
     #[allow(dead_code)]
     pub fn stack_current(&self) -> &VmStack {
-        if self.stacks.is_empty() {
-            &self.global
-        } else {
-            &self.stacks.get(0).unwrap()
+        match self.stacks.last() {
+            Some(stack) => stack,
+            None => &self.global,
         }
     }
 
     pub fn stack_current_mut(&mut self) -> &mut VmStack {
-        if self.stacks.is_empty() {
-            &mut self.global
-        } else {
-            self.stacks.get_mut(0).unwrap()
+        match self.stacks.last_mut() {
+            Some(stack) => stack,
+            None => &mut self.global,
         }
     }
 
     pub fn stack_for_var(&self, name: &SharedStr) -> &VmStack {
-        let mut iter = self.stacks.iter();
+        let mut iter = self.stacks.iter().rev();
         while let Some(stack) = iter.next() {
             if stack.var_exist(name) {
                 return stack;
@@ -229,7 +502,7 @@ impl LoxVM {
     }
 
     pub fn stack_for_var_mut(&mut self, name: &SharedStr) -> &mut VmStack {
-        let mut iter = self.stacks.iter_mut();
+        let mut iter = self.stacks.iter_mut().rev();
         while let Some(stack) = iter.next() {
             if stack.var_exist(name) {
                 return stack;
@@ -249,11 +522,18 @@ impl LoxVM {
         self.stack_current_mut().var_add(name, obj)
     }
 
-    #[allow(dead_code)]
-    pub fn var_add_all(&mut self, mut params: Vec<SharedStr>, mut args: Vec<ObjectRc>) {
-        while !params.is_empty() && !args.is_empty() {
-            let name = params.remove(0);
-            let obj = args.remove(0);
+    /**
+     * add a new variable to global, overwrite if named variable exist
+     *
+     * name: target variable name
+     * obj: value
+     */
+    pub fn global_add(&mut self, name: SharedStr, obj: ObjectRc) {
+        self.global.var_add(name, obj)
+    }
+
+    pub fn var_add_all(&mut self, params: Vec<SharedStr>, args: Vec<ObjectRc>) {
+        for (name, obj) in params.into_iter().zip(args) {
             self.var_add(name, obj);
         }
     }
@@ -0,0 +1,211 @@
+extern crate lox_lib;
+
+use std::{
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use lox_lib::{LoxParser, VmLimits};
+
+/*
+ * data-driven tests, every `.lox' file under the test directory is
+ * executed and checked with the annotations in its comments:
+ *
+ *   // expect: <line>                  a line printed by the script
+ *   // expect runtime error: <msg>     the script fails while running
+ *   // error at '<token>': <msg>       the script fails to parse
+ *   // [line N] Error at '<token>': <msg>
+ *
+ * error messages only need to be contained in the error we got, and the
+ * token of a parse error must be quoted in it as `<token>`.
+ *
+ * LOX_TEST_DIR runs another directory, such as the test suite of
+ * Crafting Interpreters. LOX_TEST_REPORT_ONLY=1 prints the summary
+ * without failing the test.
+ */
+
+const TEST_DIR: &str = "tests/lox";
+
+#[derive(Debug, Default)]
+struct Expectation {
+    output: Vec<String>,
+    runtime_error: Option<String>,
+    compile_error: Option<(Option<String>, String)>,
+}
+
+impl Expectation {
+    fn parse(code: &str) -> Self {
+        let mut expect = Self::default();
+        let mut in_string = false;
+        for line in code.lines() {
+            let comment = match Self::comment_start(line, &mut in_string) {
+                Some(idx) => line[idx + 2..].trim(),
+                None => continue,
+            };
+            if let Some(out) = comment.strip_prefix("expect:") {
+                expect.output.push(out.trim().to_string());
+            } else if let Some(msg) = comment.strip_prefix("expect runtime error:") {
+                expect.runtime_error = Some(msg.trim().to_string());
+            } else if let Some(msg) = Self::compile_error(comment) {
+                expect.compile_error = Some(msg);
+            }
+        }
+        expect
+    }
+
+    /*
+     * find the `//' of a line comment, skipping over string literals
+     *
+     * in_string: whether the line starts inside a string, strings can
+     *            span lines, updated to the state at the end of the line
+     */
+    fn comment_start(line: &str, in_string: &mut bool) -> Option<usize> {
+        let mut chars = line.char_indices().peekable();
+        while let Some((idx, ch)) = chars.next() {
+            match ch {
+                '"' => *in_string = !*in_string,
+                '/' if !*in_string && chars.peek().is_some_and(|(_, next)| *next == '/') => {
+                    return Some(idx);
+                },
+                _ => (),
+            }
+        }
+        None
+    }
+
+    // ret: the token if the annotation names one, and the message
+    fn compile_error(comment: &str) -> Option<(Option<String>, String)> {
+        let comment = match comment.strip_prefix("[line ") {
+            Some(rest) => rest.split_once("] ")?.1,
+            None => comment,
+        };
+        let lower = comment.to_lowercase();
+        if !lower.starts_with("error at") && !lower.starts_with("error:") {
+            return None;
+        }
+        let (token, msg) = match comment.find("': ") {
+            Some(idx) => {
+                let token = comment[..idx].split_once('\'').map(|(_, tk)| tk.to_string());
+                (token, &comment[idx + 3..])
+            },
+            None => (None, comment.split_once(':')?.1),
+        };
+        Some((token, msg.trim().to_string()))
+    }
+}
+
+fn limits() -> VmLimits {
+    VmLimits {
+        max_steps: Some(50_000_000),
+        timeout: Some(Duration::from_secs(10)),
+    }
+}
+
+fn run(code: &str, limits: VmLimits) -> Result<(), String> {
+    let expect = Expectation::parse(code);
+    let mut lox = LoxParser::new_test();
+    lox.limits_set(limits);
+
+    let stmts = match lox.parse_all(code) {
+        Ok(stmts) => stmts,
+        Err(err) => return match &expect.compile_error {
+            Some((Some(token), _)) if !err.contains(&format!("`{}`", token)) => {
+                Err(format!("expected compile error at `{}`, got `{}`", token, err))
+            },
+            Some((_, msg)) if err.contains(msg.as_str()) => Ok(()),
+            Some((_, msg)) => Err(format!("expected compile error `{}`, got `{}`", msg, err)),
+            None => Err(format!("unexpected compile error: {}", err)),
+        },
+    };
+    if let Some((_, msg)) = &expect.compile_error {
+        return Err(format!("expected compile error `{}`, but it parsed", msg));
+    }
+
+    let mut result = Ok(());
+    for stmt in stmts {
+        result = lox.exec_stmt(stmt);
+        if result.is_err() {
+            break;
+        }
+    }
+
+    let output = lox.console_take();
+    let output: Vec<&str> = output.lines().collect();
+    if output != expect.output {
+        return Err(format!("expected output {:?}, got {:?}", expect.output, output));
+    }
+
+    match (result, &expect.runtime_error) {
+        (Ok(()), None) => Ok(()),
+        (Ok(()), Some(msg)) => Err(format!("expected runtime error `{}`", msg)),
+        (Err(err), None) => Err(format!("unexpected runtime error: {}", err)),
+        (Err(err), Some(msg)) if err.contains(msg.as_str()) => Ok(()),
+        (Err(err), Some(msg)) => Err(format!("expected runtime error `{}`, got `{}`", msg, err)),
+    }
+}
+
+fn lox_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => panic!("cannot read {}: {}", dir.display(), err),
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            lox_files(&path, files);
+        } else if path.extension().is_some_and(|ext| ext == "lox") {
+            files.push(path);
+        }
+    }
+}
+
+#[test]
+fn test_conformance() {
+    let dir = std::env::var("LOX_TEST_DIR").unwrap_or(TEST_DIR.to_string());
+    let report_only = std::env::var("LOX_TEST_REPORT_ONLY").is_ok_and(|val| val == "1");
+
+    let mut files = Vec::new();
+    lox_files(Path::new(&dir), &mut files);
+    files.sort();
+
+    // keep the panic message of a crashed script out of the report
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+
+    let mut failed = Vec::new();
+    for file in files.iter() {
+        let code = fs::read_to_string(file).unwrap();
+        let result = match panic::catch_unwind(AssertUnwindSafe(|| run(&code, limits()))) {
+            Ok(result) => result,
+            Err(_) => Err("interpreter panicked".to_string()),
+        };
+        if let Err(msg) = result {
+            failed.push((file, msg));
+        }
+    }
+    panic::set_hook(hook);
+
+    for (file, msg) in failed.iter() {
+        println!("FAIL {}: {}", file.display(), msg);
+    }
+    println!("conformance: {} passed, {} failed, {} total",
+        files.len() - failed.len(), failed.len(), files.len());
+
+    assert!(!files.is_empty(), "no test found in {}", dir);
+    assert!(report_only || failed.is_empty(), "{} conformance tests failed", failed.len());
+}
+
+#[test]
+fn test_conformance_runner() {
+    let short = VmLimits {
+        max_steps: None,
+        timeout: Some(Duration::from_millis(50)),
+    };
+    assert_eq!(run("while (true) {} // expect runtime error: Timeout", short), Ok(()));
+
+    assert_eq!(run("print \"a // b\"; // expect: a // b", limits()), Ok(()));
+    assert_eq!(run("fun (a) {} // error at '(': function name expected", limits()), Ok(()));
+    assert!(run("fun (a) {} // error at 'a': function name expected", limits()).is_err());
+}
@@ -1,7 +1,9 @@
 
 extern crate lox_lib;
 
-use lox_lib::LoxParser;
+use std::{cell::RefCell, collections::HashMap, io::Write, rc::Rc, thread, time::{Duration, Instant}};
+
+use lox_lib::{BigInt, BufferMode, DecimalMode, ExactDecimal, FromLox, GcConfig, IntoLox, LimitExceeded, LoxError, LoxParser, OutputChannel, OverflowMode, PrinterSink, Value, VmLimits};
 
 #[test]
 fn test_calc() {
@@ -195,3 +197,830 @@ fn test_function() {
     assert_eq!(lox.console_take(), "entered fn1\n1\nentered fn2\n1\nentered fn3\n1\n1\nentered fn4\n1\n2\n3\n4\nentered fn4\n5\n6\n7\n8\n9\nentered fn4\n10\n11\n12\n13\n14\nentered outside\nentered inside\nentered inside\n0\nentered inside\n0\n1\n");
 }
 
+
+#[test]
+fn test_gc() {
+    let mut lox = LoxParser::new_test();
+    lox.exec_code("
+        var n = 0;
+        for (var i=0; i<100; i=i+1) {
+            n = n + i;
+        }
+        print gc();
+        print n;
+    ");
+    // numbers and strings hold no references, they are left to `Rc'
+    assert_eq!(lox.console_take(), "0\n4950\n");
+    assert_eq!(lox.gc_stats().collections, 1);
+    assert_eq!(lox.gc_stats().allocated, 0);
+
+    lox.gc_config_set(GcConfig { threshold: 0, ..GcConfig::default() });
+    lox.exec_code("print gc(); print gc();");
+    assert_eq!(lox.console_take(), "0\n0\n");
+    // with no threshold, every native call collects too
+    assert_eq!(lox.gc_stats().collections, 5);
+    assert_eq!(lox.gc_config().threshold, 0);
+}
+
+#[test]
+fn test_gc_cycles() {
+    let mut lox = LoxParser::new_test();
+    lox.native_add("new_list", Vec::<i64>::new);
+    lox.native_add("new_map", HashMap::<String, i64>::new);
+    lox.exec_code("
+        {
+            var self_ref = new_list();
+            push(self_ref, 1);
+            push(self_ref, self_ref);
+        }
+        var map = new_map();
+        var list = new_list();
+        set(map, \"list\", list);
+        push(list, map);
+        map = nil;
+        list = nil;
+        print gc();
+    ");
+    // containers are tracked when created, and freed by breaking the cycles
+    assert_eq!(lox.console_take(), "3\n");
+    let stats = lox.gc_stats();
+    assert_eq!((stats.allocated, stats.cycles, stats.freed, stats.live), (3, 3, 3, 0));
+
+    // reachable cycles and cycles held by the host are kept
+    lox.exec_code("
+        var keep = new_list();
+        push(keep, 1);
+        push(keep, keep);
+        var held = new_list();
+        push(held, 2);
+        push(held, held);
+        var temp = new_list();
+    ");
+    let held = lox.global_get("held").unwrap();
+    lox.exec_code("
+        held = nil;
+        temp = nil;
+        print gc();
+        print keep;
+        print keep == keep;
+    ");
+    // `temp' is released by `Rc', without breaking a cycle
+    assert_eq!(lox.console_take(), "1\n[1, [...]]\ntrue\n");
+    assert_eq!(held.to_string(), "[2, [...]]");
+    assert_eq!(lox.gc_stats().cycles, 3);
+    drop(held);
+    lox.exec_code("print gc();");
+    assert_eq!(lox.console_take(), "1\n");
+    assert_eq!(lox.gc_stats().cycles, 4);
+
+    let stats = lox.gc_stats();
+    lox.gc_config_set(GcConfig { threshold: 16, ..GcConfig::default() });
+    lox.exec_code("
+        for (var i=0; i<100; i=i+1) {
+            var ring = new_list();
+            push(ring, ring);
+        }
+    ");
+    let after = lox.gc_stats();
+    assert!(after.collections > stats.collections + 1);
+    assert!(after.cycles > stats.cycles + 50);
+
+    let err = lox.exec_line("push(1, 2);").unwrap_err();
+    assert!(err.contains("push: expect List, got Integer"), "{}", err);
+    let err = lox.exec_line("set(keep, 5, 0);").unwrap_err();
+    assert!(err.contains("list index 5 out of range"), "{}", err);
+}
+
+#[test]
+fn test_function_identity() {
+    let mut lox = LoxParser::new_test();
+    lox.exec_code("
+        fun f() {}
+        fun h() {}
+        var g = f;
+        print f == g;
+        print f == h;
+        print f != h;
+
+        var first;
+        for (var i=0; i<3; i=i+1) {
+            fun k() {}
+            if (first == nil) first = k;
+            print first == k;
+        }
+    ");
+    assert_eq!(lox.console_take(), "true\nfalse\ntrue\ntrue\ntrue\ntrue\n");
+}
+
+#[test]
+fn test_stack_overflow() {
+    let mut lox = LoxParser::new_test();
+    let err = lox.exec_line("
+        var depth = 0;
+        fun recurse() {
+            depth = depth + 1;
+            {
+                if (true) recurse();
+            }
+        }
+        recurse();
+    ").unwrap_err();
+    assert!(err.contains("StackOverflow: maximum call depth 1024 exceeded"));
+    assert!(err.contains("at recurse (repeated 1024 times)"));
+
+    // the vm is still usable after the error
+    lox.exec_code("
+        print depth;
+        var after = 1;
+        print after;
+    ");
+    assert_eq!(lox.console_take(), "1024\n1\n");
+
+    lox.call_depth_max_set(10);
+    lox.exec_code("
+        fun count(n) {
+            print n;
+            if (n > 0) count(n - 1);
+        }
+        count(9);
+    ");
+    assert_eq!(lox.console_take(), "9\n8\n7\n6\n5\n4\n3\n2\n1\n0\n");
+    assert!(lox.exec_line("count(10);").unwrap_err().contains("StackOverflow"));
+}
+
+#[test]
+fn test_limits() {
+    let mut lox = LoxParser::new_test();
+    lox.limits_set(VmLimits { max_steps: Some(1000), ..VmLimits::default() });
+    let err = lox.exec_line("
+        var n = 0;
+        fun spin() {
+            while (true) { n = n + 1; }
+        }
+        spin();
+    ").unwrap_err();
+    assert!(err.contains("StepLimitExceeded"));
+    assert_eq!(lox.limit_exceeded(), Some(LimitExceeded::Steps));
+
+    // budgets are per run and the vm can be used again
+    lox.exec_line("print n > 0;").unwrap();
+    assert_eq!(lox.limit_exceeded(), None);
+    assert!(lox.steps_used() < 1000);
+
+    lox.limits_set(VmLimits { timeout: Some(Duration::from_millis(20)), ..VmLimits::default() });
+    assert!(lox.exec_line("spin();").unwrap_err().contains("Timeout"));
+    assert_eq!(lox.limit_exceeded(), Some(LimitExceeded::Timeout));
+
+    lox.limits_set(VmLimits::default());
+    let handle = lox.interrupt_handle();
+    let stopper = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        handle.interrupt();
+    });
+    assert!(lox.exec_line("spin();").unwrap_err().contains("Interrupted"));
+    assert_eq!(lox.limit_exceeded(), Some(LimitExceeded::Interrupted));
+    stopper.join().unwrap();
+
+    lox.exec_line("print \"still alive\";").unwrap();
+    assert_eq!(lox.console_take(), "true\nstill alive\n");
+
+    // every entry point is a run with the budgets
+    lox.limits_set(VmLimits { timeout: Some(Duration::from_millis(20)), ..VmLimits::default() });
+    lox.exec_code("spin();");
+    assert_eq!(lox.limit_exceeded(), Some(LimitExceeded::Timeout));
+    for stmt in lox.parse_all("print n > 0; spin();").unwrap() {
+        let _ = lox.exec_stmt(stmt);
+    }
+    assert_eq!(lox.console_take(), "true\n");
+    assert_eq!(lox.limit_exceeded(), Some(LimitExceeded::Timeout));
+    assert!(matches!(lox.call("spin", &[]), Err(LoxError::Limit(LimitExceeded::Timeout, _))));
+}
+
+#[test]
+fn test_repl_multi_line() {
+    let mut lox = LoxParser::new_test();
+    let lines = [
+        ("fun add(a,\n", false),
+        ("        b) {\n", false),
+        ("    print a + b;\n", false),
+        ("}\n", true),
+        ("add(1,\n", false),
+        ("2);\n", true),
+        ("print \"first line\n", false),
+        ("second line\";\n", true),
+        ("var n = 1; print n;\n", true),
+        // quotes in comments don't start a string
+        ("print 1; // it\"s\n", true),
+    ];
+    for (line, done) in lines {
+        assert_eq!(lox.repl_feed(line), Ok(done), "line: {}", line);
+        assert_eq!(lox.repl_is_continuing(), !done);
+    }
+    assert_eq!(lox.console_take(), "3\nfirst line\nsecond line\n1\n1\n");
+
+    // failed input is discarded
+    assert!(lox.repl_feed("print 1 +;\n").is_err());
+    assert!(!lox.repl_is_continuing());
+    assert_eq!(lox.repl_feed("print 2;\n"), Ok(true));
+    assert_eq!(lox.console_take(), "2\n");
+
+    // a missing `;' fails on the next line which can't go on with it
+    assert_eq!(lox.repl_feed("var x = 3\n"), Ok(false));
+    assert!(lox.repl_feed("print x;\n").is_err());
+    assert!(!lox.repl_is_continuing());
+    assert_eq!(lox.repl_feed("var x = 3\n"), Ok(false));
+    assert!(lox.repl_feed("x\n").is_err());
+    assert!(!lox.repl_is_continuing());
+    assert_eq!(lox.repl_feed("print 3;\n"), Ok(true));
+    assert_eq!(lox.console_take(), "3\n");
+}
+
+#[test]
+fn test_repl_commands() {
+    let mut lox = LoxParser::new_test();
+    assert_eq!(lox.repl_feed("var b = \"str\";\n"), Ok(true));
+    assert_eq!(lox.repl_feed("var a = 1 + 2;\n"), Ok(true));
+    assert!(lox.repl_feed("print c;\n").is_err());
+
+    lox.command_exec(".vars").unwrap();
+    assert_eq!(lox.console_take(), "a = 3\nb = str\n");
+
+    lox.command_exec(".tokens print 1;").unwrap();
+    assert_eq!(lox.console_take(), "Print\nNumber(Integer(1))\nSemicolon\n");
+
+    lox.command_exec(".mode max_steps 100").unwrap();
+    lox.command_exec(".mode max_steps").unwrap();
+    assert_eq!(lox.console_take(), "max_steps      100\n");
+    assert!(lox.command_exec(".mode no_such_option 1").is_err());
+    lox.command_exec(".mode max_steps off").unwrap();
+
+    // only the successful input is saved
+    let path = std::env::temp_dir().join(format!("lox_session_{}.lox", std::process::id()));
+    let path = path.to_str().unwrap();
+    lox.command_exec(&format!(".save {}", path)).unwrap();
+    assert_eq!(std::fs::read_to_string(path).unwrap(), "var b = \"str\";\nvar a = 1 + 2;\n");
+
+    lox.command_exec(".reset").unwrap();
+    assert!(lox.exec_line("print a;").is_err());
+    lox.command_exec(&format!(".load {}", path)).unwrap();
+    lox.exec_line("print a;").unwrap();
+    assert_eq!(lox.console_take(), "3\n");
+    std::fs::remove_file(path).unwrap();
+
+    lox.command_add("double", "<n>  print n * 2", |lox, args| {
+        lox.exec_line(&format!("print {} * 2;", args))
+    });
+    lox.command_exec(".double 21").unwrap();
+    assert_eq!(lox.console_take(), "42\n");
+    lox.command_exec(".help").unwrap();
+    assert!(lox.console_take().contains(".double  <n>  print n * 2\n"));
+    assert!(lox.command_exec(".nothing").is_err());
+}
+
+#[test]
+fn test_repl_echo() {
+    let mut lox = LoxParser::new_test();
+    let lines = [
+        ("1 + 2;\n", true),
+        ("\"a\" + \"b\"\n", true),
+        ("var n = 5\n", false),
+        (";\n", true),
+        ("n\n", true),
+        ("n = n +\n", false),
+        ("1\n", true),
+        ("nil\n", true),
+        ("print \"printed\"\n", false),
+        (";\n", true),
+    ];
+    for (line, done) in lines {
+        assert_eq!(lox.repl_feed(line), Ok(done), "line: {}", line);
+    }
+    assert_eq!(lox.console_take(), "3\n\"ab\"\n5\n6\nprinted\n");
+
+    // scripts don't echo
+    lox.exec_line("n; 1 + 2;").unwrap();
+    assert!(lox.exec_line("n\n").is_err());
+    assert_eq!(lox.console_take(), "");
+}
+
+#[derive(Clone, Default)]
+struct SharedWriter(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_output_sink() {
+    let mut lox = LoxParser::new_test();
+    let writer = SharedWriter::default();
+    lox.output_sink_set(OutputChannel::Stdout, PrinterSink::Writer(Box::new(writer.clone())));
+    lox.exec_code("print 1; print \"two\";");
+    assert_eq!(String::from_utf8(writer.0.borrow().clone()).unwrap(), "1\ntwo\n");
+    assert_eq!(lox.console_take(), "");
+
+    let lines = Rc::new(RefCell::new(Vec::new()));
+    let lines_cb = lines.clone();
+    lox.output_mode_set(BufferMode::Line);
+    lox.output_sink_set(OutputChannel::Stdout, PrinterSink::Callback(Box::new(move |out| {
+        lines_cb.borrow_mut().push(out.to_string());
+    })));
+    lox.exec_code("for (var i = 0; i < 3; i = i + 1) print i;");
+    assert_eq!(*lines.borrow(), vec!["0\n", "1\n", "2\n"]);
+
+    // switching back to buffer keeps the output for `console_take`
+    lox.output_sink_set(OutputChannel::Stdout, PrinterSink::Buffer);
+    lox.exec_code("print 3;");
+    assert_eq!(lox.console_take(), "3\n");
+    assert_eq!(lines.borrow().len(), 3);
+    assert_eq!(lox.console_take_err(), "");
+}
+
+#[test]
+fn test_embed_call() {
+    let mut lox = LoxParser::new_test();
+    lox.exec_code("
+    fun handler(n) {
+        if (n > 2) return n * 2;
+        return \"small\";
+    }
+    fun fail() { print undefined_var; }
+    fun spin() { while (true) {} }
+    var count = 1;
+    ");
+    assert_eq!(lox.call("handler", &[Value::from(3)]), Ok(Value::from(6)));
+    assert_eq!(lox.call("handler", &[Value::from(1)]).unwrap().as_str(), Some("small"));
+
+    assert_eq!(lox.call("missing", &[]), Err(LoxError::Undefined("missing".to_string())));
+    assert_eq!(lox.call("count", &[]), Err(LoxError::NotCallable("count".to_string())));
+    assert_eq!(lox.call("handler", &[]), Err(LoxError::Arity {
+        name: "handler".to_string(), expected: 1, got: 0,
+    }));
+    assert!(matches!(lox.call("fail", &[]), Err(LoxError::Runtime(_))));
+
+    lox.limits_set(VmLimits { max_steps: Some(1000), timeout: None });
+    assert!(matches!(lox.call("spin", &[]), Err(LoxError::Limit(LimitExceeded::Steps, _))));
+    lox.limits_set(VmLimits::default());
+
+    // the vm is still usable after errors
+    assert_eq!(lox.call("handler", &[Value::from(5)]).unwrap().as_i64(), Some(10));
+}
+
+#[test]
+fn test_embed_globals() {
+    let mut lox = LoxParser::new_test();
+    lox.global_set("limit", 10);
+    lox.global_set("name", "lox");
+    lox.exec_code("var doubled = limit * 2; print name;");
+    assert_eq!(lox.console_take(), "lox\n");
+    assert_eq!(lox.global_get("doubled").and_then(|val| val.as_i64()), Some(20));
+    assert_eq!(lox.global_get("nothing"), None);
+
+    lox.global_set("doubled", ());
+    assert!(lox.global_get("doubled").unwrap().is_nil());
+}
+
+#[test]
+fn test_convert() {
+    assert_eq!(i64::from_lox(&42.into_lox()), Ok(42));
+    assert_eq!(f64::from_lox(&3.into_lox()), Ok(3.0));
+    assert_eq!(String::from_lox(&"lox".into_lox()), Ok("lox".to_string()));
+    assert_eq!(Option::<bool>::from_lox(&().into_lox()), Ok(None));
+    assert_eq!(Vec::<i64>::from_lox(&vec![1, 2, 3].into_lox()), Ok(vec![1, 2, 3]));
+
+    let map = HashMap::from([("a".to_string(), vec![1.5]), ("b".to_string(), vec![])]);
+    assert_eq!(HashMap::<String, Vec<f64>>::from_lox(&map.clone().into_lox()), Ok(map));
+
+    let err = i64::from_lox(&"lox".into_lox()).unwrap_err();
+    assert!(err.contains("type mismatch: expect Integer, got String"), "{}", err);
+    let err = u8::from_lox(&300.into_lox()).unwrap_err();
+    assert!(err.contains("out of range"), "{}", err);
+    let err = Vec::<i64>::from_lox(&vec![Some(1), None].into_lox()).unwrap_err();
+    assert!(err.contains("got Nil (at index 1)"), "{}", err);
+
+    // integers beyond i64 are kept exact
+    assert_eq!(u64::from_lox(&u64::MAX.into_lox()), Ok(u64::MAX));
+    assert_eq!(u128::from_lox(&u128::MAX.into_lox()), Ok(u128::MAX));
+    assert_eq!(u64::MAX.into_lox().to_string(), "18446744073709551615");
+    let err = u64::from_lox(&u128::MAX.into_lox()).unwrap_err();
+    assert!(err.contains("integer 340282366920938463463374607431768211455 out of range for u64"), "{}", err);
+    let err = i64::from_lox(&u64::MAX.into_lox()).unwrap_err();
+    assert!(err.contains("out of range for i64"), "{}", err);
+}
+
+#[test]
+fn test_native_typed() {
+    let mut lox = LoxParser::new_test();
+    lox.native_add("longer", |len: i64, str: String| str.len() as i64 > len);
+    lox.native_add("words", |str: String| str.split(' ').map(String::from).collect::<Vec<_>>());
+    lox.native_add("checked_div", |a: i64, b: i64| match b {
+        0 => Err("division by zero".to_string()),
+        _ => Ok(a / b),
+    });
+    lox.native_add("or_default", |val: Option<i64>| val.unwrap_or(-1));
+    lox.native_add("square", |val: i64| val as i128 * val as i128);
+    lox.native_add("u128_max", || u128::MAX);
+    lox.exec_code("
+    print longer(3, \"lox!\");
+    print words(\"a b\");
+    print checked_div(7, 2);
+    print or_default(nil);
+    print square(-9223372036854775807 - 1);
+    print u128_max();
+    ");
+    assert_eq!(lox.console_take(), concat!(
+        "true\n[\"a\", \"b\"]\n3\n-1\n",
+        "85070591730234615865843651857942052864\n",
+        "340282366920938463463374607431768211455\n",
+    ));
+
+    let err = lox.exec_line("longer(\"3\", \"lox\");").unwrap_err();
+    assert!(err.contains("argument 1 of `longer`: ") && err.contains("expect Integer, got String"), "{}", err);
+    let err = lox.exec_line("checked_div(1, 0);").unwrap_err();
+    assert!(err.contains("division by zero"), "{}", err);
+    let err = lox.exec_line("words();").unwrap_err();
+    assert!(err.contains("expect 1 arguments, got 0"), "{}", err);
+}
+
+#[test]
+fn test_json() {
+    let mut lox = LoxParser::new_test();
+    lox.global_set("text", r#"{"name": "lox", "tags": ["a", 1, 2.5, null], "ok": true}"#);
+    lox.exec_code("var data = json_parse(text); print data; print json_stringify(data, nil);");
+    assert_eq!(lox.console_take(), concat!(
+        "{\"name\": \"lox\", \"ok\": true, \"tags\": [\"a\", 1, 2.5, (Nil)]}\n",
+        "{\"name\":\"lox\",\"ok\":true,\"tags\":[\"a\",1,2.5,null]}\n",
+    ));
+
+    // values can be serialized from the host too
+    let data = lox.global_get("data").unwrap();
+    let json = serde_json::to_string(&data).unwrap();
+    assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), data);
+
+    lox.exec_code("fun f() {}");
+    assert!(serde_json::to_string(&lox.global_get("f").unwrap()).is_err());
+
+    // a container shared twice is fine, a cycle is not
+    lox.exec_code("var item = json_parse(\"[1]\"); var pair = json_parse(\"[]\"); push(pair, item); push(pair, item);");
+    lox.exec_code("print json_stringify(pair, nil); push(item, pair);");
+    assert_eq!(lox.console_take(), "[[1],[1]]\n");
+    let err = lox.exec_line("print json_stringify(pair, nil);").unwrap_err();
+    assert!(err.contains("cannot serialize a cyclic List"), "{}", err);
+
+    lox.exec_code("var max = json_parse(\"18446744073709551615\"); print max; print max - 1;");
+    assert_eq!(lox.console_take(), "18446744073709551615\n18446744073709551614\n");
+    let max = lox.global_get("max").unwrap();
+    assert_eq!(serde_json::to_string(&max).unwrap(), "18446744073709551615");
+
+    // exact decimals keep all their digits as strings
+    lox.decimal_mode_set(DecimalMode::Exact);
+    lox.exec_code("var price = 0.1 + 0.2; print json_stringify(price, nil);");
+    assert_eq!(lox.console_take(), "\"0.3\"\n");
+    let price: ExactDecimal = lox.global_get("price").unwrap().get().unwrap();
+    let json = serde_json::to_string(&lox.global_get("price").unwrap()).unwrap();
+    let text: String = serde_json::from_str::<Value>(&json).unwrap().get().unwrap();
+    assert_eq!(text.parse::<ExactDecimal>().unwrap(), price);
+}
+
+#[test]
+fn test_pattern_cache() {
+    let mut lox = LoxParser::new_test();
+    lox.exec_code("
+    var count = 0;
+    for (var i = 0; i < 10; i++) {
+        if (re_match(\"^\\d+$\", \"${i}\") != nil) count++;
+    }
+    print count;
+    print re_split(\"\\d+\", \"a1b22c\");
+    ");
+    assert_eq!(lox.console_take(), "10\n[\"a\", \"b\", \"c\"]\n");
+    // the pattern used in the loop is compiled once
+    assert_eq!(lox.pattern_cache_len(), 2);
+
+    let mut other = LoxParser::new_test();
+    assert_eq!(other.pattern_cache_len(), 0);
+    other.exec_code("re_match(\"a\", \"a\");");
+    assert_eq!(other.pattern_cache_len(), 1);
+    assert_eq!(lox.pattern_cache_len(), 2);
+
+    assert!(lox.exec_line("re_match(\"(\", \"a\");").is_err());
+    assert_eq!(lox.pattern_cache_len(), 2);
+}
+
+#[test]
+fn test_number_literal() {
+    let mut lox = LoxParser::new_test();
+    // the last token of the input is not lost
+    assert_eq!(lox.repl_feed("0x2a"), Ok(true));
+    assert_eq!(lox.console_take(), "42\n");
+}
+
+#[test]
+fn test_overflow() {
+    let mut lox = LoxParser::new_test();
+    lox.overflow_mode_set(OverflowMode::Error);
+    lox.exec_code("var max = 9223372036854775807; var min = -max - 1; var n = 0;");
+    // a failed operation leaves the variable as it was
+    let err = lox.exec_line("n = max * 2;").unwrap_err();
+    assert!(err.contains("integer overflow: 9223372036854775807 * 2"), "{}", err);
+    let err = lox.exec_line("n = min - 1;").unwrap_err();
+    assert!(err.contains("integer overflow: -9223372036854775808 - 1"), "{}", err);
+    let err = lox.exec_line("n = min / -1;").unwrap_err();
+    assert!(err.contains("integer overflow: -9223372036854775808 / -1"), "{}", err);
+    let err = lox.exec_line("n = -min;").unwrap_err();
+    assert!(err.contains("integer overflow: -(-9223372036854775808)"), "{}", err);
+    lox.exec_code("print n;");
+    assert_eq!(lox.console_take(), "0\n");
+
+    lox.overflow_mode_set(OverflowMode::Decimal);
+    lox.exec_code("print max + 1; print -min; print max - 1;");
+    assert_eq!(lox.console_take(), "9.223372036854776e18\n9.223372036854776e18\n9223372036854775806\n");
+
+    lox.command_exec(".mode overflow error").unwrap();
+    assert_eq!(lox.overflow_mode(), OverflowMode::Error);
+    assert!(lox.exec_line("print max + 1;").is_err());
+}
+
+#[test]
+fn test_bigint() {
+    let mut lox = LoxParser::new_test();
+    // integers are promoted by default
+    assert_eq!(lox.overflow_mode(), OverflowMode::BigInt);
+    lox.exec_code("
+    var max = 9223372036854775807;
+    var big = max * max;
+    print big;
+    print big / max == max;
+    print big - max * max;
+    print big > max;
+    print -(-max - 1);
+    print big + 0.5 > max;
+    var fact = 1;
+    for (var i = 1; i <= 25; i = i + 1) fact = fact * i;
+    print fact;
+    ");
+    assert_eq!(lox.console_take(), concat!(
+        "85070591730234615847396907784232501249\n",
+        "true\n",
+        "0\n",
+        "true\n",
+        "9223372036854775808\n",
+        "true\n",
+        "15511210043330985984000000\n",
+    ));
+
+    // results that fit in i64 are plain integers again
+    assert_eq!(lox.global_get("fact").unwrap().as_i64(), None);
+    lox.exec_code("var back = (max + 1) - 1;");
+    assert_eq!(lox.global_get("back").unwrap().as_i64(), Some(i64::MAX));
+
+    let fact: BigInt = lox.global_get("fact").unwrap().get().unwrap();
+    assert_eq!(fact.to_string(), "15511210043330985984000000");
+    lox.global_set("huge", Value::from_rc(fact.into_lox()));
+    lox.exec_code("print huge / 1000000 * 1000000 == huge;");
+    assert_eq!(lox.console_take(), "true\n");
+}
+
+#[test]
+fn test_exact_decimal() {
+    let mut lox = LoxParser::new_test();
+    lox.exec_code("print 0.1 + 0.2 == 0.3;");
+    assert_eq!(lox.console_take(), "false\n");
+
+    lox.decimal_mode_set(DecimalMode::Exact);
+    lox.exec_code("
+    var price = 19.99;
+    print 0.1 + 0.2 == 0.3;
+    print 0.1 + 0.2;
+    print price * 3;
+    print price * 3 - 59.97 == 0;
+    print 1.0 / 3;
+    print 2.5e-3 < 1;
+    print to_float(0.5) + 1;
+    print to_exact(0.25 + to_float(0.5)) == 0.75;
+    ");
+    assert_eq!(lox.console_take(), concat!(
+        "true\n",
+        "0.3\n",
+        "59.97\n",
+        "true\n",
+        "0.3333333333333333333333333333\n",
+        "true\n",
+        "1.5\n",
+        "true\n",
+    ));
+
+    let price: ExactDecimal = lox.global_get("price").unwrap().get().unwrap();
+    assert_eq!(price.to_string(), "19.99");
+    let err = lox.exec_line("print 79228162514264337593543950335.0 * 10;").unwrap_err();
+    assert!(err.contains("decimal overflow"), "{}", err);
+
+    lox.command_exec(".tokens 0.5").unwrap();
+    assert_eq!(lox.console_take(), "Number(Exact(0.5))\n");
+
+    lox.command_exec(".mode decimal float").unwrap();
+    assert_eq!(lox.decimal_mode(), DecimalMode::Float);
+}
+
+#[test]
+fn test_format() {
+    let mut lox = LoxParser::new_test();
+    lox.decimal_mode_set(DecimalMode::Exact);
+    lox.exec_code("print format(\"{:.2}\", 2.675); print format(\"{}\", 1.50);");
+    assert_eq!(lox.console_take(), "2.68\n1.50\n");
+
+    lox.global_set("inf", f64::INFINITY);
+    lox.exec_code("var nan = sqrt(-1.0); print nan; print format(\"{} {:.2} {}\", nan, inf, -inf);");
+    assert_eq!(lox.console_take(), "nan\nnan inf -inf\n");
+}
+
+#[test]
+fn test_math() {
+    let mut lox = LoxParser::new_test();
+    lox.overflow_mode_set(OverflowMode::BigInt);
+    lox.decimal_mode_set(DecimalMode::Exact);
+    lox.exec_code("
+    print pow(2, 100);
+    print abs(-9223372036854775807 - 1);
+    print pow(1.5, 2);
+    print round(2.345);
+    print floor(-0.5);
+    ");
+    assert_eq!(lox.console_take(), "1267650600228229401496703205376\n9223372036854775808\n2.25\n2\n-1\n");
+
+    // the sequence only depends on the seed
+    let mut rolls = Vec::new();
+    for _ in 0..2 {
+        lox.random_seed(7);
+        lox.exec_code("print random_int(1, 100); print random_int(1, 100);");
+        rolls.push(lox.console_take());
+    }
+    assert_eq!(rolls[0], rolls[1]);
+
+    let err = lox.exec_line("random_int(3, 1);").unwrap_err();
+    assert!(err.contains("empty range"), "{}", err);
+    let err = lox.exec_line("min();").unwrap_err();
+    assert!(err.contains("too few arguments"), "{}", err);
+
+    // huge powers fail before computing the exact result
+    let begin = Instant::now();
+    lox.overflow_mode_set(OverflowMode::Error);
+    let err = lox.exec_line("print pow(3, 20000000);").unwrap_err();
+    assert!(err.contains("integer overflow"), "{}", err);
+    lox.overflow_mode_set(OverflowMode::BigInt);
+    let err = lox.exec_line("print pow(3, 20000000);").unwrap_err();
+    assert!(err.contains("integer too large"), "{}", err);
+    lox.overflow_mode_set(OverflowMode::Decimal);
+    lox.exec_code("print pow(3, 20000000);");
+    assert_eq!(lox.console_take(), "inf\n");
+    assert!(begin.elapsed() < Duration::from_secs(1), "{:?}", begin.elapsed());
+    lox.overflow_mode_set(OverflowMode::BigInt);
+
+    // builtins are not listed by `.vars', unless redefined
+    lox.exec_code("var PI = 3;");
+    lox.command_exec(".vars").unwrap();
+    assert_eq!(lox.console_take(), "PI = 3\n");
+}
+
+#[test]
+fn test_string() {
+    let mut lox = LoxParser::new_test();
+    let mut map = HashMap::new();
+    map.insert("name".to_string(), "lox");
+    lox.global_set("m", Value::from_rc(map.into_lox()));
+    lox.exec_code("
+    print m[\"name\"].upper();
+    print m[\"none\"];
+    print len(m);
+    var s = \"日本語\";
+    print s[1] + s[-3];
+    print s[1:][0];
+    ");
+    assert_eq!(lox.console_take(), "LOX\n(Nil)\n1\n本日\n本\n");
+
+    let err = lox.exec_line("print s[1.5];").unwrap_err();
+    assert!(err.contains("bad index"), "{}", err);
+    let err = lox.exec_line("print s[\"a\":];").unwrap_err();
+    assert!(err.contains("bad slice bound"), "{}", err);
+    let err = lox.exec_line("print 3[0];").unwrap_err();
+    assert!(err.contains("cannot index Integer"), "{}", err);
+    let err = lox.exec_line("print \"ab\".repeat(-1);").unwrap_err();
+    assert!(err.contains("negative count"), "{}", err);
+    let err = lox.exec_line("print \"ab\".repeat(9223372036854775807);").unwrap_err();
+    assert!(err.contains("result longer than"), "{}", err);
+    let err = lox.exec_line("print \"ab\".repeat(100000000);").unwrap_err();
+    assert!(err.contains("result longer than"), "{}", err);
+    lox.exec_code("print \"\".repeat(9223372036854775807) == \"\";");
+    assert_eq!(lox.console_take(), "true\n");
+    assert!(lox.exec_line("print s[];").is_err());
+
+    // methods of a type use the builtin, other functions are found as globals
+    lox.native_add("shout", |str: String| format!("{}!", str.to_uppercase()));
+    // missing methods are in tests/lox/errors/no_method
+    lox.exec_code("
+    fun twice(str) { return str + str; }
+    var upper = nil;
+    print \"hi\".shout(); print \"ab\".twice(); print \"ab\".upper();
+    ");
+    assert_eq!(lox.console_take(), "HI!\nabab\nAB\n");
+}
+
+#[test]
+fn test_interpolation() {
+    let mut lox = LoxParser::new_test();
+    lox.exec_code("
+    var items = 3;
+    print \"${items} item${items}\";
+    print \"no ${\"${items}\"} nesting limit\";
+    ");
+    assert_eq!(lox.console_take(), "3 item3\nno 3 nesting limit\n");
+
+    // parse errors point to the embedded expression
+    let err = lox.exec_line("print \"total: ${items +}\";").unwrap_err();
+    assert!(err.contains("in `${items +}` at column 8 of the string"), "{}", err);
+    let err = lox.exec_line("print \"ab${items items}\";").unwrap_err();
+    assert!(err.contains("in `${items items}` at column 3"), "{}", err);
+    let err = lox.exec_line("print \"${}\";").unwrap_err();
+    assert!(err.contains("empty expression"), "{}", err);
+    let err = lox.exec_line("print \"${items\";").unwrap_err();
+    assert!(err.contains("end without close mark"), "{}", err);
+
+    // runtime errors too
+    let err = lox.exec_line("print \"a ${1 / 0} b ${nope}\";").unwrap_err();
+    assert!(err.contains("in `${1 / 0}` at column 3"), "{}", err);
+}
+
+#[test]
+fn test_compound_assign() {
+    let mut lox = LoxParser::new_test();
+    lox.exec_code("
+    var a=7;a%=4;print a;
+    var b=1;b-=-2;print b;
+    var c=3;c*=c+1;print c;
+    fun bump() { c = 100; return 1; }
+    c += bump();
+    print c;
+    ");
+    // `c' is read before the right side is evaluated
+    assert_eq!(lox.console_take(), "3\n3\n12\n13\n");
+
+    // only the hidden division of `i64::MIN % -1' overflows
+    lox.exec_code("var min = -9223372036854775807 - 1; print min % -1;");
+    lox.overflow_mode_set(OverflowMode::Decimal);
+    lox.exec_code("print min % -1; min %= -1; print min;");
+    lox.overflow_mode_set(OverflowMode::Error);
+    assert_eq!(lox.console_take(), "0\n0\n0\n");
+
+    // `++' and `--' only apply to a variable next to them
+    lox.exec_code("var x = 3; print 1--1; print - -x; print --x; print x; print (x)--1;");
+    assert_eq!(lox.console_take(), "2\n3\n2\n2\n3\n");
+    lox.exec_code("print 1 - -x; print x++ + 1; print x;");
+    assert_eq!(lox.console_take(), "3\n3\n3\n");
+    assert!(lox.exec_line("print x--1;").is_err());
+
+    lox.exec_code("var max = 9223372036854775807;");
+    let err = lox.exec_line("max++;").unwrap_err();
+    assert!(err.contains("integer overflow"), "{}", err);
+    lox.overflow_mode_set(OverflowMode::BigInt);
+    lox.exec_code("max++; print max; max %= 10; print max;");
+    assert_eq!(lox.console_take(), "9223372036854775808\n8\n");
+
+    lox.decimal_mode_set(DecimalMode::Exact);
+    lox.exec_code("var d = 5.25; d %= 2; print d; d++; print d;");
+    assert_eq!(lox.console_take(), "1.25\n2.25\n");
+
+    let err = lox.exec_line("print 1 % 0;").unwrap_err();
+    assert!(err.contains("cannot divide by Zero"), "{}", err);
+    assert!(lox.exec_line("++1;").is_err());
+    assert!(lox.exec_line("undefined_var += 1;").is_err());
+}
+
+#[test]
+fn test_conditional() {
+    let mut lox = LoxParser::new_test();
+    let mut map = HashMap::new();
+    map.insert("port".to_string(), 8080);
+    lox.global_set("config", Value::from_rc(map.into_lox()));
+    lox.exec_code("
+    print config[\"port\"] ?? 80;
+    print config[\"host\"] ?? \"localhost\";
+    var s = \"abcdef\";
+    var short = true;
+    print s[short ? 3 : 0:];
+    print \"${short ? \"short\" : \"long\"}\";
+    var a; var b;
+    a = short ? b = 1 : 2;
+    print a; print b;
+    ");
+    assert_eq!(lox.console_take(), "8080\nlocalhost\ndef\nshort\n1\n1\n");
+
+    let err = lox.exec_line("print true ? 1;").unwrap_err();
+    assert!(err.contains("expected token :"), "{}", err);
+    assert!(lox.exec_line("print ?? 1;").is_err());
+}